@@ -17,6 +17,8 @@ use serde::{
 use serde_json::json;
 use tauri::Window;
 
+use crate::error::LauncherError;
+
 const CLIENT_ID: &str = "c1e288f4-4793-4bfd-bb9e-e3ea3e14218e";
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -53,6 +55,13 @@ struct UserRefreshRequest
     refresh_token: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+struct UserAuthenticationError
+{
+    error: String,
+    error_description: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct UserAuthenticationResponse
 {
@@ -113,19 +122,17 @@ pub struct Authenticator {
 }
 
 impl Authenticator {
-    pub async fn new(game_dir: &PathBuf, window: &Window) -> Self {
-        let profile = authenticate(&game_dir, &window)
-            .await
-            .expect("Unable to authenticate");
+    pub async fn new(game_dir: &PathBuf, window: &Window) -> Result<Self, LauncherError> {
+        let profile = authenticate(game_dir, window).await?;
 
-        Self {
+        Ok(Self {
             profile,
-        }
+        })
     }
 }
 
-pub async fn authenticate(game_dir: &PathBuf, window: &Window) -> Result<AuthResult, Box<dyn std::error::Error>> {
-    window.emit("launcher-log", "Authentification en cours");
+pub async fn authenticate(game_dir: &PathBuf, window: &Window) -> Result<AuthResult, LauncherError> {
+    window.emit("launcher-log", "Authentification en cours").ok();
     // Only one client needed
     let client = reqwest::Client::new();
 
@@ -136,12 +143,13 @@ pub async fn authenticate(game_dir: &PathBuf, window: &Window) -> Result<AuthRes
     if token_path.exists() {
         let file = File::open(&token_path)?;
         let buffer = BufReader::new(file);
-        let saved_resp: UserAuthenticationResponse = serde_json::from_reader(buffer).expect("Failed to read saved credentials");
+        let saved_resp: UserAuthenticationResponse = serde_json::from_reader(buffer)
+            .map_err(|err| LauncherError::Auth(format!("Failed to read saved credentials : {}", err)))?;
 
         let user_refresh_req = UserRefreshRequest {
             grant_type: "refresh_token".into(),
             client_id: CLIENT_ID.into(),
-            refresh_token: saved_resp.refresh_token.into(),
+            refresh_token: saved_resp.refresh_token,
         };
 
         let user_refresh_resp: UserAuthenticationResponse = 
@@ -153,8 +161,7 @@ pub async fn authenticate(game_dir: &PathBuf, window: &Window) -> Result<AuthRes
             .json()
             .await?;
 
-        fs::write(&token_path, serde_json::to_string(&user_refresh_resp)
-                  .expect("Failed to save authentication token"))?;
+        fs::write(&token_path, serde_json::to_string(&user_refresh_resp)?)?;
 
     } else {
 
@@ -180,20 +187,20 @@ pub async fn authenticate(game_dir: &PathBuf, window: &Window) -> Result<AuthRes
 
         //println!("{}", serde_json::to_string_pretty(&device_auth_resp)?);
 
-        window.emit("launcher-log", format!("{}", &device_auth_resp.message));
+        window.emit("launcher-log", &device_auth_resp.message).ok();
 
         // User Authentication
         let user_auth_req = UserAuthenticationRequest {
             grant_type: "urn:ietf:params:oauth:grant-type:device_code".into(),
             client_id: CLIENT_ID.into(),
-            device_code: device_auth_resp.device_code.into(),
+            device_code: device_auth_resp.device_code,
         };
 
         //println!("{}", serde_json::to_string_pretty(&user_auth_req)?);
 
         // Polling until the user authenticates
         let user_auth_resp: UserAuthenticationResponse = loop {
-            std::thread::sleep(std::time::Duration::from_secs(3));
+            tokio::time::sleep(std::time::Duration::from_secs(device_auth_resp.interval.max(1) as u64)).await;
 
             let response = 
                 client.post(format!("https://login.microsoftonline.com/{tenant}/oauth2/v2.0/token",
@@ -203,21 +210,28 @@ pub async fn authenticate(game_dir: &PathBuf, window: &Window) -> Result<AuthRes
                 .await?;
 
             if response.status() == StatusCode::OK {
-                window.emit("launcher-log", "Authentification en cours");
+                window.emit("launcher-log", "Authentification en cours").ok();
                 break response.json()
                     .await?;
 
             }
+
+            // Anything but "the user hasn't finished yet" ends the flow
+            let error: UserAuthenticationError = response.json().await?;
+            match error.error.as_str() {
+                "authorization_pending" | "slow_down" => continue,
+                _ => return Err(LauncherError::Auth(error.error_description.unwrap_or(error.error))),
+            }
         };
 
-        fs::write(&token_path, serde_json::to_string(&user_auth_resp)
-                  .expect("Failed to save authentication token"))?;
+        fs::write(&token_path, serde_json::to_string(&user_auth_resp)?)?;
         //println!("{}", serde_json::to_string_pretty(&user_auth_resp)?);
     }
 
     let file = File::open(&token_path)?;
     let buffer = BufReader::new(file);
-    let saved_resp: UserAuthenticationResponse = serde_json::from_reader(buffer).expect("Failed to read saved credentials");
+    let saved_resp: UserAuthenticationResponse = serde_json::from_reader(buffer)
+        .map_err(|err| LauncherError::Auth(format!("Failed to read saved credentials : {}", err)))?;
 
     // XBOXLIVE AUTHENTICATION
     let xbox_auth_req = json!({
@@ -266,9 +280,15 @@ pub async fn authenticate(game_dir: &PathBuf, window: &Window) -> Result<AuthRes
     //println!("{}", serde_json::to_string_pretty(&minecraft_xsts_resp)?);
 
     // MINECRAFT AUTHENTICATION
+    let userhash = minecraft_xsts_resp.display_claims
+        .get("xui")
+        .and_then(|xui| xui.first())
+        .and_then(|claims| claims.get("uhs"))
+        .ok_or_else(|| LauncherError::Auth("The XSTS token doesn't contain a user hash".into()))?;
+
     let minecraft_auth_req = json!({
         "identityToken": format!("XBL3.0 x={userhash};{xsts_token}",
-                                 userhash = &minecraft_xsts_resp.display_claims["xui"][0]["uhs"], 
+                                 userhash = userhash, 
                                  xsts_token = &minecraft_xsts_resp.token),
                                  "ensureLegacyEnabled" : true
     });
@@ -313,8 +333,7 @@ pub async fn authenticate(game_dir: &PathBuf, window: &Window) -> Result<AuthRes
 
     let mut profile_path = PathBuf::from(&game_dir);
     profile_path.push("profile.json");
-    fs::write(profile_path, serde_json::to_string(&result)
-              .expect("Failed to save authentication data"))?;
+    fs::write(profile_path, serde_json::to_string(&result)?)?;
 
     Ok(result)
 }
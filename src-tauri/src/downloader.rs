@@ -1,25 +1,64 @@
 use std::{
     fs,
+    io,
     println,
-    path::PathBuf,
+    path::{Path, PathBuf},
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
 use reqwest::{
+    header::RANGE,
     StatusCode,
     Client,
 };
 
 use serde_json::Value;
+use sha1::{Digest, Sha1};
+use tokio::{
+    io::AsyncWriteExt,
+    sync::Semaphore,
+    task::JoinSet,
+};
 use url::Url;
 
-struct Download {
-    url: String,
-    path: PathBuf,
+use crate::error::LauncherError;
+
+use self::progress::Progress;
+
+pub use self::progress::{DownloadProgress, ProgressSink};
+
+mod progress;
+
+// Default limits for download_queue, the per-host one keeps us polite with
+// resources.download.minecraft.net when the whole asset index is queued
+const MAX_DOWNLOADS: usize = 32;
+const MAX_DOWNLOADS_PER_HOST: usize = 16;
+const MAX_DOWNLOADS_ENV: &str = "PBLM_MAX_DOWNLOADS";
+const MAX_DOWNLOADS_PER_HOST_ENV: &str = "PBLM_MAX_DOWNLOADS_PER_HOST";
+const RETRY_DELAY: Duration = Duration::from_secs(3);
+const RETRIES: usize = 9;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+pub struct Download {
+    pub url: String,
+    pub path: PathBuf,
+    pub sha1: Option<String>,
+    pub size: Option<u64>,
 }
 
 pub struct Downloader {
     client: Client,
-    queue: Vec<Download>
+    queue: Vec<Download>,
+    pub max_downloads: usize,
+    pub max_downloads_per_host: usize,
+}
+
+impl Default for Downloader {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Downloader {
@@ -29,161 +68,352 @@ impl Downloader {
         Self {
             client,
             queue,
+            max_downloads: MAX_DOWNLOADS,
+            max_downloads_per_host: MAX_DOWNLOADS_PER_HOST,
+        }
+    }
+
+    pub fn with_limits(max_downloads: usize, max_downloads_per_host: usize) -> Self {
+        Self {
+            max_downloads: max_downloads.max(1),
+            max_downloads_per_host: max_downloads_per_host.max(1),
+            ..Self::new()
         }
     }
 
-    pub fn push_to_queue(&mut self, url: String, path: PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+    // The limits from PBLM_MAX_DOWNLOADS and PBLM_MAX_DOWNLOADS_PER_HOST, the defaults otherwise
+    pub fn from_env() -> Self {
+        let limit = |name: &str, default: usize| std::env::var(name)
+            .ok()
+            .and_then(|limit| limit.trim().parse().ok())
+            .unwrap_or(default);
+        Self::with_limits(limit(MAX_DOWNLOADS_ENV, MAX_DOWNLOADS),
+                          limit(MAX_DOWNLOADS_PER_HOST_ENV, MAX_DOWNLOADS_PER_HOST))
+    }
+
+    pub fn push_to_queue(&mut self, url: String, path: PathBuf, sha1: Option<String>, size: Option<u64>) -> Result<(), LauncherError> {
+        // Two downloads writing the same .part would corrupt each other
+        if self.queue.iter().any(|queued| queued.path == path) {
+            return Ok(())
+        }
+
         let download = Download {
             url,
             path,
+            sha1,
+            size,
         };
 
         self.queue.push(download);
         Ok(())
     }
 
-    pub async fn download_file(&self, url: String, path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
-        for _n in (1..10).rev() {
-            let name = match Url::parse(&url) {
-                Ok(result) => result,
+    // What download_queue will fetch, in no particular order
+    pub fn queued(&self) -> &[Download] {
+        &self.queue
+    }
+
+    pub async fn download_file(&self, url: String, path: &Path) -> Result<(), LauncherError> {
+        let download = Download {
+            url,
+            path: path.to_path_buf(),
+            sha1: None,
+            size: None,
+        };
+
+        download_file(&self.client, &download, &Progress::new(1, 0)).await
+    }
+
+    pub async fn to_text(&self, url: String) -> Result<String, LauncherError> {
+        for _n in (0..RETRIES).rev() {
+            let response = match self.client.get(&url).send().await.and_then(|resp| resp.error_for_status()) {
+                Ok(resp) => resp,
+                // Asking again won't make a 404 appear
+                Err(err) if err.status().is_some_and(|status| status.is_client_error()) => return Err(err.into()),
                 Err(err) => {
-                    println!("Failed to parse URL: {}", err);
-                    std::thread::sleep(std::time::Duration::from_secs(3));
+                    println!("Error requesting text: {}", err);
+                    tokio::time::sleep(RETRY_DELAY).await;
                     continue
                 }
             };
-
-            let name = match name.path_segments() {
-                Some(result) => result,
-                None => { 
-                    println!("Failed to parse URL, retrying...");
-                    std::thread::sleep(std::time::Duration::from_secs(3));
-                    continue 
-                } 
-            };
             
-            let name = match name.last() {
-                Some(result) => result,
-                None => {
-                    println!("Failed to parse name from URL, retrying...");
-                    std::thread::sleep(std::time::Duration::from_secs(3));
+            match response.text().await {
+                Ok(resp) => return Ok(resp),
+                Err(err) => {
+                    println!("Error converting request to text: {}", err);
+                    tokio::time::sleep(RETRY_DELAY).await;
                     continue
                 }
             };
+        }
 
-            let mut full_path = PathBuf::from(path.clone());
-            full_path.push(name);
-
-            if full_path.is_file() {
-                println!("File already downloaded, skipping...");
-                break
-            }
+        Err(LauncherError::Network(format!("Failed to fetch {} after {} attempts", url, RETRIES)))
+    }
 
-            let response = match self.client.get(&url).send().await {
+    pub async fn to_json(&self, url: String) -> Result<Value, LauncherError> {
+        for _n in (0..RETRIES).rev() {
+            let response = match self.client.get(&url).send().await.and_then(|resp| resp.error_for_status()) {
                 Ok(resp) => resp,
+                // Asking again won't make a 404 appear
+                Err(err) if err.status().is_some_and(|status| status.is_client_error()) => return Err(err.into()),
                 Err(err) => {
-                    println!("Error while sending download request: {}", err);
-                    std::thread::sleep(std::time::Duration::from_secs(3));
+                    println!("Error requesting text: {}", err);
+                    tokio::time::sleep(RETRY_DELAY).await;
                     continue
                 }
             };
-
-            if response.status() != StatusCode::OK {
-                println!("The request didn't respond with the correct status code: {}", response.status());
-                std::thread::sleep(std::time::Duration::from_secs(3));
-                continue
-            }
-
-            let body = match response.bytes().await {
-                Ok(resp) => resp,
+            
+            match response.json().await {
+                Ok(resp) => return Ok(resp),
                 Err(err) => {
-                    println!("Failed to convert response to bytes, retrying... : {} ", err);
-                    std::thread::sleep(std::time::Duration::from_secs(3));
-                    continue 
+                    println!("Error converting request to json: {}", err);
+                    tokio::time::sleep(RETRY_DELAY).await;
+                    continue
                 }
             };
+        }
 
-            if !path.exists() {
-                fs::create_dir_all(path) 
-                    .expect("Failed to create destination directory");
-            }
+        Err(LauncherError::Network(format!("Failed to fetch {} after {} attempts", url, RETRIES)))
+    }
+
+    pub async fn download_queue(&mut self, sink: &dyn ProgressSink) -> Result<(), LauncherError> {
+        let total_bytes = self.queue.iter().filter_map(|file| file.size).sum();
+        let progress = Arc::new(Progress::new(self.queue.len(), total_bytes));
+        sink.progress(&progress.snapshot());
+
+        let downloads = Arc::new(Semaphore::new(self.max_downloads));
+        let mut hosts: HashMap<String, Arc<Semaphore>> = HashMap::new();
+        let mut tasks = JoinSet::new();
 
-            fs::write(&full_path, &body)
-                .expect("Failed to create file");
+        while let Some(file) = self.queue.pop() {
+            let host = match Url::parse(&file.url) {
+                Ok(url) => url.host_str().unwrap_or_default().to_string(),
+                Err(_) => String::new(),
+            };
 
-            println!("{} downloaded", &full_path.to_str().expect("Filename contains invalid unicode (failed to convert path to str)"));
+            let host_downloads = hosts
+                .entry(host)
+                .or_insert_with(|| Arc::new(Semaphore::new(self.max_downloads_per_host)))
+                .clone();
+            let downloads = downloads.clone();
+            let client = self.client.clone();
+            let progress = progress.clone();
 
-            break
+            tasks.spawn(async move {
+                // Waiting on the host first so a busy host doesn't hold global slots
+                let _host_permit = host_downloads.acquire_owned().await.ok();
+                let _permit = downloads.acquire_owned().await.ok();
+                download_file(&client, &file, &progress).await
+            });
         }
 
-        Ok(())
+        // Letting every download finish, the first failure is reported afterwards
+        let mut failure: Option<LauncherError> = None;
+        let mut last_progress = Instant::now();
+        loop {
+            // Timing out so the progress still moves while a big file is downloading
+            let error = match tokio::time::timeout(PROGRESS_INTERVAL, tasks.join_next()).await {
+                Ok(Some(Ok(Ok(_)))) => None,
+                Ok(Some(Ok(Err(err)))) => Some(err),
+                Ok(Some(Err(err))) => Some(LauncherError::Network(format!("Download task failed : {}", err))),
+                Ok(None) => break,
+                Err(_) => None,
+            };
+
+            if let Some(err) = error {
+                println!("Failed to download file from queue : {}", err);
+                failure.get_or_insert(err);
+            }
+
+            if last_progress.elapsed() >= PROGRESS_INTERVAL {
+                sink.progress(&progress.snapshot());
+                last_progress = Instant::now();
+            }
+        }
+
+        sink.progress(&progress.snapshot());
+
+        println!("Empty queue");
+
+        match failure {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
     }
+}
+
+async fn download_file(client: &Client, download: &Download, progress: &Progress) -> Result<(), LauncherError> {
+    let url = &download.url;
+    let mut checksum_failed: Option<PathBuf> = None;
+    let mut size_known = download.size.is_some();
+    // Bytes of this file currently counted in the progress
+    let mut counted: u64 = 0;
+
+    let full_path = &download.path;
+    let name = full_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let part_path = full_path.with_file_name(format!("{}.part", name));
+
+    for _n in (0..RETRIES).rev() {
+        progress.started_file(&name);
+
+        if full_path.is_file() {
+            if verify_file(full_path, download)? {
+                println!("File already downloaded, skipping...");
+                progress.reset_file(counted, fs::metadata(full_path)?.len());
+                progress.completed_file();
+                return Ok(())
+            }
+
+            println!("{} doesn't match its checksum, downloading it again", full_path.display());
+            fs::remove_file(full_path)?;
+        }
+
+        if let Some(parent) = full_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // Whatever a previous attempt (or a previous launch) left in the .part file
+        let mut existing = match fs::metadata(&part_path) {
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        };
 
-    pub async fn to_text(&self, url: String) -> Result<String, Box<dyn std::error::Error>> {
-        let mut text: String = String::new();
+        if download.size.is_some_and(|size| existing > size) {
+            fs::remove_file(&part_path)?;
+            existing = 0;
+        }
 
-        for _n in (0..10).rev() {
-            let response = match self.client.get(&url).send().await {
+        progress.reset_file(counted, existing);
+        counted = existing;
+
+        if download.size != Some(existing) || existing == 0 {
+            let mut request = client.get(url);
+            if existing > 0 {
+                request = request.header(RANGE, format!("bytes={}-", existing));
+            }
+
+            let mut response = match request.send().await {
                 Ok(resp) => resp,
                 Err(err) => {
-                    println!("Error requesting text: {}", err);
-                    std::thread::sleep(std::time::Duration::from_secs(3));
+                    println!("Error while sending download request: {}", err);
+                    tokio::time::sleep(RETRY_DELAY).await;
                     continue
                 }
             };
-            
-            let response = match response.text().await {
-                Ok(resp) => resp,
-                Err(err) => {
-                    println!("Error converting request to text: {}", err);
-                    std::thread::sleep(std::time::Duration::from_secs(3));
+
+            let append = match response.status() {
+                StatusCode::PARTIAL_CONTENT if existing > 0 => true,
+                StatusCode::OK => {
+                    // The server ignored the range, starting over
+                    progress.reset_file(counted, 0);
+                    counted = 0;
+                    false
+                },
+                StatusCode::RANGE_NOT_SATISFIABLE => {
+                    println!("Unable to resume {}, starting over", full_path.display());
+                    fs::remove_file(&part_path)?;
+                    continue
+                },
+                status => {
+                    println!("The request didn't respond with the correct status code: {}", status);
+                    tokio::time::sleep(RETRY_DELAY).await;
                     continue
                 }
             };
 
-            text = response;
-        }
+            if !size_known {
+                progress.add_total(counted + response.content_length().unwrap_or_default());
+                size_known = true;
+            }
 
-        Ok(text)
-    }
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(append)
+                .truncate(!append)
+                .open(&part_path)
+                .await?;
 
-    pub async fn to_json(&self, url: String) -> Result<Value, Box<dyn std::error::Error>> {
-        let mut json: Value = Value::Null;
-        for _n in (0..10).rev() {
-            let response = match self.client.get(&url).send().await {
-                Ok(resp) => resp,
-                Err(err) => {
-                    println!("Error requesting text: {}", err);
-                    std::thread::sleep(std::time::Duration::from_secs(3));
-                    continue
-                }
-            };
-            
-            let response = match response.json().await {
-                Ok(resp) => resp,
-                Err(err) => {
-                    println!("Error converting request to json: {}", err);
-                    std::thread::sleep(std::time::Duration::from_secs(3));
-                    continue
+            let received = loop {
+                match response.chunk().await {
+                    Ok(Some(chunk)) => {
+                        file.write_all(&chunk).await?;
+                        progress.received(chunk.len() as u64);
+                        counted += chunk.len() as u64;
+                    },
+                    Ok(None) => break true,
+                    Err(err) => {
+                        println!("Failed to read response body, retrying... : {} ", err);
+                        break false
+                    }
                 }
             };
 
-            json = response;
+            file.flush().await?;
+            drop(file);
+
+            if !received {
+                // Keeping the .part file, the next attempt resumes from there
+                tokio::time::sleep(RETRY_DELAY).await;
+                continue
+            }
+        }
+
+        if !verify_file(&part_path, download)? {
+            println!("{} doesn't match its checksum, retrying...", full_path.display());
+            fs::remove_file(&part_path)?;
+            checksum_failed = Some(full_path.clone());
+            tokio::time::sleep(RETRY_DELAY).await;
+            continue
         }
 
-        Ok(json)
+        tokio::fs::rename(&part_path, full_path).await?;
+
+        println!("{} downloaded", full_path.display());
+        progress.completed_file();
+
+        return Ok(())
     }
 
-    pub async fn download_queue(&mut self) {
-        loop {
-            match self.queue.pop() {
-                Some(file) => self.download_file(file.url, &file.path).await
-                    .expect("Failed to download file from queue"),
-                None => {
-                    println!("Empty queue");
-                    break
-                }
-            }
+    match checksum_failed {
+        Some(path) => Err(LauncherError::Checksum(path)),
+        None => Err(LauncherError::Network(format!("Failed to download {} after {} attempts", url, RETRIES))),
+    }
+}
+
+// Last segment of the URL path, as is
+pub fn url_file_name(url: &str) -> Option<String> {
+    Url::parse(url)
+        .ok()?
+        .path_segments()?
+        .next_back()
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+}
+
+// A file without an expected hash or size is trusted as long as it exists
+fn verify_file(path: &Path, download: &Download) -> io::Result<bool> {
+    if let Some(size) = download.size {
+        if fs::metadata(path)?.len() != size {
+            return Ok(false)
+        }
+    }
+
+    if let Some(sha1) = &download.sha1 {
+        if !file_sha1(path)?.eq_ignore_ascii_case(sha1) {
+            return Ok(false)
         }
     }
+
+    Ok(true)
+}
+
+pub fn file_sha1(path: &Path) -> io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha1::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
 }
@@ -0,0 +1,124 @@
+use std::{
+    println,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+
+use serde::Serialize;
+use tauri::Window;
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadProgress {
+    pub total_files: usize,
+    pub completed_files: usize,
+    pub total_bytes: u64,
+    pub received_bytes: u64,
+    pub current_file: Option<String>,
+    pub bytes_per_second: u64,
+    pub eta_seconds: Option<u64>,
+}
+
+pub trait ProgressSink: Send + Sync {
+    fn progress(&self, progress: &DownloadProgress);
+}
+
+impl ProgressSink for Window {
+    fn progress(&self, progress: &DownloadProgress) {
+        if let Err(err) = self.emit("download-progress", progress.clone()) {
+            println!("Failed to emit download progress : {}", err);
+        }
+    }
+}
+
+// Shared between the download tasks, the queue turns it into DownloadProgress events
+pub struct Progress {
+    started: Instant,
+    total_files: usize,
+    completed_files: AtomicUsize,
+    total_bytes: AtomicU64,
+    received_bytes: AtomicU64,
+    // Only what actually went through the network, files already on disk
+    // would make the throughput meaningless
+    transferred_bytes: AtomicU64,
+    current_file: Mutex<Option<String>>,
+}
+
+impl Progress {
+    pub fn new(total_files: usize, total_bytes: u64) -> Self {
+        Self {
+            started: Instant::now(),
+            total_files,
+            completed_files: AtomicUsize::new(0),
+            total_bytes: AtomicU64::new(total_bytes),
+            received_bytes: AtomicU64::new(0),
+            transferred_bytes: AtomicU64::new(0),
+            current_file: Mutex::new(None),
+        }
+    }
+
+    pub fn add_total(&self, bytes: u64) {
+        self.total_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn received(&self, bytes: u64) {
+        self.received_bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.transferred_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    // Brings the bytes counted for a file back to what is actually on disk,
+    // either a file that was already there or a .part file being resumed or dropped
+    pub fn reset_file(&self, counted: u64, on_disk: u64) {
+        if on_disk >= counted {
+            self.received_bytes.fetch_add(on_disk - counted, Ordering::Relaxed);
+        } else {
+            self.received_bytes.fetch_sub(counted - on_disk, Ordering::Relaxed);
+        }
+    }
+
+    pub fn started_file(&self, name: &str) {
+        if let Ok(mut current_file) = self.current_file.lock() {
+            *current_file = Some(name.to_string());
+        }
+    }
+
+    pub fn completed_file(&self) {
+        self.completed_files.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> DownloadProgress {
+        let total_bytes = self.total_bytes.load(Ordering::Relaxed);
+        let received_bytes = self.received_bytes.load(Ordering::Relaxed);
+        let transferred_bytes = self.transferred_bytes.load(Ordering::Relaxed);
+
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let bytes_per_second = if elapsed > 0.0 {
+            (transferred_bytes as f64 / elapsed) as u64
+        } else {
+            0
+        };
+
+        let eta_seconds = match bytes_per_second {
+            0 => None,
+            speed => Some(total_bytes.saturating_sub(received_bytes) / speed),
+        };
+
+        let current_file = match self.current_file.lock() {
+            Ok(current_file) => current_file.clone(),
+            Err(_) => None,
+        };
+
+        DownloadProgress {
+            total_files: self.total_files,
+            completed_files: self.completed_files.load(Ordering::Relaxed),
+            total_bytes,
+            received_bytes,
+            current_file,
+            bytes_per_second,
+            eta_seconds,
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use std::{
+    io,
+    path::PathBuf,
+};
+
+use serde::{
+    ser::SerializeStruct,
+    Serialize,
+    Serializer,
+};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum LauncherError {
+    #[error("Network error : {0}")]
+    Network(String),
+    #[error("{} doesn't match its checksum", .0.display())]
+    Checksum(PathBuf),
+    #[error("Authentication failed : {0}")]
+    Auth(String),
+    #[error("Filesystem error : {0}")]
+    Filesystem(#[from] io::Error),
+    #[error("Unable to parse manifest : {0}")]
+    ManifestParse(String),
+    #[error("Invalid signature : {0}")]
+    Signature(String),
+    #[error("Java was not found, is it installed ?")]
+    JavaNotFound,
+    #[error("Incompatible mods : {0}")]
+    ModConflict(String),
+    #[error("Loader installation failed : {0}")]
+    LoaderInstall(String),
+    #[error("The game exited with code {}", .0.map_or("unknown".to_string(), |code| code.to_string()))]
+    ProcessExit(Option<i32>),
+    // Something players have to set up themselves, like an API key
+    #[error("Configuration error : {0}")]
+    Config(String),
+}
+
+impl LauncherError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LauncherError::Network(_) => "network",
+            LauncherError::Checksum(_) => "checksum",
+            LauncherError::Auth(_) => "auth",
+            LauncherError::Filesystem(_) => "filesystem",
+            LauncherError::ManifestParse(_) => "manifestParse",
+            LauncherError::Signature(_) => "signature",
+            LauncherError::JavaNotFound => "javaNotFound",
+            LauncherError::ModConflict(_) => "modConflict",
+            LauncherError::LoaderInstall(_) => "loaderInstall",
+            LauncherError::ProcessExit(_) => "processExit",
+            LauncherError::Config(_) => "config",
+        }
+    }
+}
+
+impl From<reqwest::Error> for LauncherError {
+    fn from(err: reqwest::Error) -> Self {
+        LauncherError::Network(err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for LauncherError {
+    fn from(err: serde_json::Error) -> Self {
+        LauncherError::ManifestParse(err.to_string())
+    }
+}
+
+impl From<zip::result::ZipError> for LauncherError {
+    fn from(err: zip::result::ZipError) -> Self {
+        match err {
+            zip::result::ZipError::Io(err) => LauncherError::Filesystem(err),
+            err => LauncherError::Filesystem(std::io::Error::new(std::io::ErrorKind::InvalidData, err)),
+        }
+    }
+}
+
+// Sent to the front-end as { kind, message } when a command fails
+impl Serialize for LauncherError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("LauncherError", 2)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
@@ -1,4 +1,5 @@
 use std::{
+    collections::{BTreeMap, HashMap},
     path::{Path, PathBuf}, 
     println
 };
@@ -6,9 +7,22 @@ use std::{
 use serde_json::Value;
 use tauri::Window;
 
-use crate::{fabric, mods};
+use crate::{
+    downloader,
+    error::LauncherError,
+    loader::{forge::ForgeInstall, ModLoader},
+    maven::MavenCoordinate,
+    modrinth::ModrinthApi,
+    pack::{InstallSide, Pack, PackMod},
+};
 
 use self::{
+    java::JavaRuntime,
+    lockfile::{LockedFile, LockedNative, LockedOptionalMod, LockedPlatform, Lockfile},
+    managed_mods::ManagedMods,
+    natives::NativeLibrary,
+    optional_mods::OptionalMods,
+    rules::Environment,
     version_manifest::VersionManifest, 
     version_file::VersionFile
 };
@@ -16,7 +30,15 @@ use self::{
 use super::downloader::Downloader;
 
 mod version_manifest;
-mod version_file;
+pub mod version_file;
+mod natives;
+pub mod rules;
+pub mod java;
+mod managed_mods;
+pub mod lockfile;
+pub mod fabric_mods;
+pub mod optional_mods;
+pub mod server;
 
 const VERSION_MANIFEST: &str = "https://piston-meta.mojang.com/mc/game/version_manifest_v2.json";
 
@@ -25,193 +47,599 @@ pub struct Installer {
     pub game: GameInfo,
     pub sys: SysInfo,
     pub downloader: Downloader,
+    // Filled while installing, in the order the launcher should use it
+    pub classpath: Vec<PathBuf>,
+    pub version_file: Option<VersionFile>,
+    // The managed runtime once installed, the system one until then
+    pub java: PathBuf,
+    natives: Vec<NativeLibrary>,
+    forge: Option<ForgeInstall>,
 }
 
 pub struct GameInfo {
-    pub minecraft_version: String,
+    pub pack: Pack,
+    pub side: InstallSide,
 }
 
 pub struct SysInfo {
     pub os: String,
+    // Assets, libraries and runtimes, shared by every instance
     pub game_dir: PathBuf,
+    // Where the game runs, with its mods and configs. The game directory itself for the main pack
+    pub instance_dir: PathBuf,
+}
+
+// Created on first use
+pub fn game_dir() -> Result<PathBuf, LauncherError> {
+    let home_dir = dirs::home_dir()
+        .ok_or_else(|| LauncherError::Filesystem(std::io::Error::new(
+                    std::io::ErrorKind::NotFound, 
+                    "Failed to locate home directory")))?;
+    
+    let game_dir = Path::new(&home_dir).join(".pblm/");
+    if !game_dir.exists() {
+        std::fs::create_dir(&game_dir)?;
+        println!("Successfully created game directory");
+    }
+    Ok(game_dir)
 }
 
 impl Installer {
-    pub fn new(minecraft_version: &str) -> Self {
-        let downloader = Downloader::new();
+    pub fn new(pack: Pack, instance_dir: PathBuf, side: InstallSide) -> Result<Self, LauncherError> {
+        let downloader = Downloader::from_env();
         let os_type = std::env::consts::OS;
-
-        let home_dir = match os_type {
-            "linux" => dirs::home_dir().expect("Failed to locate home directory"),
-            "macos" => dirs::home_dir().expect("Failed to locate home directory"),
-            "windows" => dirs::home_dir().expect("Failed to locate AppData"),
-            _ => panic!("Unsupported OS")
-        };
-        
-        let game_dir = Path::new(&home_dir).join(".pblm/");
-        if !game_dir.exists() {
-            match std::fs::create_dir(&game_dir) {
-                Ok(path) => {
-                    println!("Successfully created game directory");
-                    path
-                },
-                Err(err) => println!("Unable to create game directory : {}", err),
-            };
-        }
+        let game_dir = game_dir()?;
+        std::fs::create_dir_all(&instance_dir)?;
 
         let sys = SysInfo {
             os: os_type.to_string(),
             game_dir,
+            instance_dir,
         };
 
         let game = GameInfo {
-            minecraft_version: minecraft_version.to_string(),
+            pack,
+            side,
         };
 
-        Self { 
+        Ok(Self { 
             game, 
             sys, 
             downloader,
+            classpath: Vec::new(),
+            version_file: None,
+            java: java::system_java(),
+            natives: Vec::new(),
+            forge: None,
+        })
+    }
+
+    // What lockfile paths are relative to : the game directory for players, the server
+    // directory for servers, which keeps its own runtime so that it can be moved as is
+    fn root_dir(&self) -> &Path {
+        match self.game.side {
+            InstallSide::Client => &self.sys.game_dir,
+            InstallSide::Server => &self.sys.instance_dir,
         }
     }
 
-    pub async fn install(&mut self, window: &Window) -> Result<(), Box<dyn std::error::Error>> {
-            window.emit("launcher-log", "Installation en cours...")
-            .expect("Failed to log");
+    pub fn natives_dir(&self) -> PathBuf {
+        let mut path = PathBuf::from(&self.sys.game_dir);
+        path.push(format!("versions/{}/natives", self.game.pack.minecraft_version));
+        path
+    }
+
+    pub async fn install(&mut self, window: &Window) -> Result<(), LauncherError> {
+        window.emit("launcher-log", "Installation en cours...").ok();
+        self.classpath.clear();
+        self.natives.clear();
+        self.forge = None;
 
+        if let Some(lock) = self.game.pack.lock.clone() {
+            let lockfile = Lockfile::fetch(&self.downloader, &lock.url, &lock.sha1).await?;
+            return self.install_locked(window, lockfile).await
+        }
+
+        let version_file = self.fetch_version_file().await?;
+        let version_file = self.install_loader(version_file).await?;
+        println!("Loader profile merged successfully");
+        
+        self.install_assets(&version_file).await?;
+        println!("Assets added to queue successfully");
+
+        self.install_libraries(&version_file)?;
+        println!("Libraries added to queue successfully");
+           
+        self.install_client(&version_file)?;
+        println!("Client added to queue successfully");
+
+        self.install_log_config(&version_file)?;
+
+        let runtime = self.install_java(&version_file).await?;
+
+        let resolved = self.resolve_mods().await?;
+        let mods = self.install_mods(resolved.clone())?;
+        println!("Mods added to queue successfully");
+
+        // Taken before downloading, the queue is empty afterwards
+        let mut lockfile = self.lockfile(&version_file, runtime.as_ref(), &resolved)?;
+
+        self.downloader.download_queue(window).await?;
+        lockfile.pin_downloaded(self.root_dir())?;
+        // Only once the new ones are there, a failed download keeps the old mods playable
+        self.update_managed_mods(mods)?;
+
+        self.finish_install(window, version_file, runtime)?;
+        lockfile.save(&self.sys.instance_dir)?;
+
+        Ok(())
+    }
+
+    // The vanilla one, before any loader is merged in
+    async fn fetch_version_file(&self) -> Result<VersionFile, LauncherError> {
         let version_manifest: VersionManifest = serde_json::from_value(
-            self.downloader.to_json(VERSION_MANIFEST.to_string())
-            .await
-            .expect("Failed to fetch version manifest"))
-            .expect("Failed to convert version manifest");
+            self.downloader.to_json(VERSION_MANIFEST.to_string()).await?)?;
 
         let version_file = version_manifest
             .versions
             .into_iter()
-            .find(|ver| ver.id == self.game.minecraft_version)
-            .expect("Unable to parse version manifest");
-
-        let version_file: VersionFile = serde_json::from_value(self.downloader
-            .to_json(version_file.url)
-            .await
-            .expect("Failed to fetch version file"))
-            .expect("Failed to convert version file");
-        
-        match self.install_assets(&version_file).await {
-            Ok(_) => println!("Assets added to queue successfully"),
-            Err(err) => println!("Error while adding assets to queue : {}", err),
-        };
+            .find(|ver| ver.id == self.game.pack.minecraft_version)
+            .ok_or_else(|| LauncherError::ManifestParse(format!(
+                        "Minecraft {} is missing from the version manifest",
+                        self.game.pack.minecraft_version)))?;
 
-        match self.install_libraries(&version_file) {
-            Ok(_) => println!("Libraries added to queue successfully"),
-            Err(err) => println!("Error while adding libraries to queue : {}", err),
-        };
-           
-        match self.install_client(&version_file) {
-            Ok(_) => println!("Client added to queue successfully"),
-            Err(err) => println!("Error while adding client to queue : {}", err),
-        };
+        Ok(serde_json::from_value(self.downloader.to_json(version_file.url).await?)?)
+    }
 
-        match self.install_fabric() {
-            Ok(_) => println!("Fabric added to queue successfully"),
-            Err(err) => println!("Error while adding Fabric to queue : {}", err),
-        };
+    // No resolution at all, the files and launch settings are the lockfile's
+    async fn install_locked(&mut self, window: &Window, lockfile: Lockfile) -> Result<(), LauncherError> {
+        let platform = lockfile.platform(&Environment::current(), self.game.side)?;
+        let game_dir = self.sys.game_dir.clone();
+        let mods = self.queue_locked(&lockfile, platform)?;
+
+        self.classpath = platform.classpath
+            .iter()
+            .map(|path| lockfile::absolute(&game_dir, path))
+            .collect::<Result<_, _>>()?;
+        self.natives = platform.natives
+            .iter()
+            .map(|native| native.to_native(&game_dir))
+            .collect::<Result<_, _>>()?;
+        self.downloader.download_queue(window).await?;
+        self.update_managed_mods(mods)?;
+
+        // The installer's bundled libraries aren't downloads, they come out of the jar once it's there
+        if let Some(installer) = &lockfile.forge_installer {
+            let forge = ForgeInstall::open(lockfile::absolute(&game_dir, installer)?)?;
+            forge.queue_libraries(&mut self.downloader, &game_dir.join("libraries"))?;
+            self.downloader.download_queue(window).await?;
+            self.forge = Some(forge);
+        }
 
-        match self.install_mods() {
-            Ok(_) => println!("Mods added to queue successfully"),
-            Err(err) => println!("Error while adding mods to queue : {}", err),
-        };
-        self.downloader.download_queue().await;
+        self.finish_install(window, lockfile.version_file.clone(), platform.java.clone())?;
+        lockfile.save(&self.sys.instance_dir)?;
 
-            window.emit("launcher-log", "Installation terminée")
-            .expect("Failed to log");
+        Ok(())
+    }
 
+    // Queues every file of the lockfile for this platform, returns the file names in mods/
+    fn queue_locked(&mut self, lockfile: &Lockfile, platform: &LockedPlatform) -> Result<Vec<String>, LauncherError> {
+        let root_dir = self.root_dir().to_path_buf();
+        let mods = self.locked_mod_names(lockfile)?;
+        let mods_dir = self.locked_mods_dir()?;
+
+        for file in lockfile.files.iter().chain(&platform.files) {
+            // Disabled optional mods are downloaded under their .disabled name
+            let path = match file.path.strip_prefix(&mods_dir).and_then(|name| mods.get(name)) {
+                Some(name) => format!("{}{}", mods_dir, name),
+                None => file.path.clone(),
+            };
+            self.downloader.push_to_queue(file.url.clone(),
+                                          lockfile::absolute(&root_dir, &path)?,
+                                          file.sha1.clone(),
+                                          file.size)?;
+        }
+
+        Ok(lockfile.mods.iter().filter_map(|name| mods.get(name).cloned()).collect())
+    }
+
+    // Where mods/ is in lockfile paths, with a trailing slash
+    fn locked_mods_dir(&self) -> Result<String, LauncherError> {
+        Ok(format!("{}/", lockfile::relative(self.root_dir(), &self.sys.instance_dir.join("mods"))?))
+    }
+
+    // The name each locked mod has in mods/ once the players' choices are applied
+    fn locked_mod_names(&self, lockfile: &Lockfile) -> Result<HashMap<String, String>, LauncherError> {
+        let mods_dir = self.sys.instance_dir.join("mods");
+        let optional_mods = OptionalMods::load(&self.sys.instance_dir)?;
+        let mut names = HashMap::new();
+
+        for name in &lockfile.mods {
+            let optional = lockfile.optional_mods.iter().find(|optional| optional.file == *name);
+            let installed = match optional {
+                Some(optional) => {
+                    let enabled = optional_mods.is_enabled(&optional.key, optional.default_enabled);
+                    optional_mods::apply(&mods_dir, name, enabled)?
+                },
+                None => name.clone(),
+            };
+            names.insert(name.clone(), installed);
+        }
+        Ok(names)
+    }
+
+    // Once everything is downloaded
+    fn finish_install(&mut self, window: &Window, version_file: VersionFile, runtime: Option<JavaRuntime>) -> Result<(), LauncherError> {
+        if let Some(runtime) = runtime {
+            runtime.finish(self.root_dir())?;
+            self.java = runtime.java(self.root_dir());
+        }
+
+        if let Some(forge) = &self.forge {
+            window.emit("launcher-log", "Installation du mod loader...").ok();
+            forge.run_processors(&self.java, &self.sys.game_dir, &self.client_path(&version_file))?;
+            println!("Loader processors ran successfully");
+        }
+
+        self.extract_natives()?;
+        println!("Natives extracted successfully");
+
+        self.check_mods(window, &version_file)?;
+
+        self.version_file = Some(version_file);
+
+        window.emit("launcher-log", "Installation terminée").ok();
+
+        Ok(())
+    }
+
+    // Only reported, Fabric gives the details when it refuses to start
+    fn check_mods(&self, window: &Window, version_file: &VersionFile) -> Result<(), LauncherError> {
+        if self.game.pack.loader.as_ref().map(|loader| loader.kind) != Some(ModLoader::Fabric) {
+            return Ok(())
+        }
+
+        let mut provided = vec![
+            ("minecraft".to_string(), self.game.pack.minecraft_version.clone()),
+            ("java".to_string(), version_file.java_version
+                .as_ref()
+                .map_or("8".to_string(), |java| java.major_version.to_string())),
+        ];
+        let loader_version = version_file.libraries
+            .iter()
+            .find_map(|lib| lib.name.strip_prefix("net.fabricmc:fabric-loader:"));
+        if let Some(version) = loader_version {
+            provided.push(("fabricloader".to_string(), version.to_string()));
+        }
+
+        let issues = fabric_mods::check(&self.sys.instance_dir.join("mods"), &provided)?;
+        for issue in &issues {
+            println!("{}", issue);
+            window.emit("launcher-log", issue.to_string()).ok();
+        }
+        window.emit("mod-issues", &issues).ok();
         Ok(())
     }
 
-    async fn install_assets(&mut self, version_file: &VersionFile) -> Result<(), Box<dyn std::error::Error>> {
+    fn lockfile(&self, version_file: &VersionFile, runtime: Option<&JavaRuntime>, mods: &[PackMod]) -> Result<Lockfile, LauncherError> {
+        let game_dir = self.root_dir();
+        let env = Environment::current();
+
+        // Optional mods are locked under their enabled name, whatever this player chose
+        let mods_dir = self.locked_mods_dir()?;
+        let mut files = self.downloader
+            .queued()
+            .iter()
+            .map(|download| LockedFile::new(game_dir, download))
+            .map(|file| file.map(|mut file| {
+                if file.path.starts_with(&mods_dir) {
+                    if let Some(path) = file.path.strip_suffix(optional_mods::DISABLED_SUFFIX) {
+                        file.path = path.to_string();
+                    }
+                }
+                file
+            }))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut forge_installer = None;
+        if let Some(forge) = &self.forge {
+            let path = lockfile::relative(game_dir, &forge.installer_jar)?;
+            if let Some(url) = &forge.installer_url {
+                files.push(LockedFile {
+                    url: url.clone(),
+                    path: path.clone(),
+                    sha1: Some(downloader::file_sha1(&forge.installer_jar)?),
+                    size: Some(std::fs::metadata(&forge.installer_jar)?.len()),
+                });
+            }
+            forge_installer = Some(path);
+        }
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        let (files, platform_files) = lockfile::split_files(version_file, files)?;
+
+        let platform = LockedPlatform {
+            classpath: self.classpath
+                .iter()
+                .map(|path| lockfile::relative(game_dir, path))
+                .collect::<Result<_, _>>()?,
+            natives: self.natives
+                .iter()
+                .map(|native| LockedNative::new(game_dir, native))
+                .collect::<Result<_, _>>()?,
+            java: runtime.cloned(),
+            files: platform_files,
+        };
+
+        Ok(Lockfile {
+            pack: self.game.pack.name.clone(),
+            pack_version: self.game.pack.version.clone(),
+            side: self.game.side,
+            version_file: version_file.clone(),
+            forge_installer,
+            mods: mods
+                .iter()
+                .map(|modif| modif.local_name())
+                .collect::<Result<_, _>>()?,
+            optional_mods: mods
+                .iter()
+                .filter(|modif| modif.optional)
+                .map(|modif| Ok(LockedOptionalMod {
+                    file: modif.local_name()?,
+                    key: modif.key()?,
+                    default_enabled: modif.default_enabled,
+                }))
+                .collect::<Result<_, LauncherError>>()?,
+            files,
+            platforms: BTreeMap::from([(lockfile::platform_key(&env), platform)]),
+        })
+    }
+
+    async fn install_assets(&mut self, version_file: &VersionFile) -> Result<(), LauncherError> {
         let mut index_path = PathBuf::from(&self.sys.game_dir);
-        index_path.push("assets/indexes");
-        self.downloader.push_to_queue(version_file.asset_index.url.clone(), index_path)
-            .expect("Failed to push asset index to queue");
+        index_path.push(format!("assets/indexes/{}.json", version_file.asset_index.id));
+        self.downloader.push_to_queue(version_file.asset_index.url.clone(), 
+                                      index_path,
+                                      Some(version_file.asset_index.sha1.clone()),
+                                      u64::try_from(version_file.asset_index.size).ok())?;
         
-        let asset_index: Value = serde_json::from_value(
-            self.downloader.to_json(version_file.asset_index.url.clone())
-            .await
-            .expect("Failed to fetch asset index"))
-            .expect("Failed to convert asset index");
+        let asset_index: Value = self.downloader
+            .to_json(version_file.asset_index.url.clone())
+            .await?;
 
         let assets = asset_index["objects"].as_object()
-            .expect("Failed to parse objects in the asset index");
+            .ok_or_else(|| LauncherError::ManifestParse("Failed to parse objects in the asset index".into()))?;
 
         for asset in assets {
             let mut path = PathBuf::new();
             let hash = asset.1["hash"].as_str()
-                .expect("Failed to parse hash from asset");
+                .ok_or_else(|| LauncherError::ManifestParse(format!("Failed to parse hash from asset {}", asset.0)))?;
+            let hash_dir = hash.get(0..2)
+                .ok_or_else(|| LauncherError::ManifestParse(format!("Invalid hash for asset {}", asset.0)))?;
             path.push(&self.sys.game_dir);
-            path.push(format!("assets/objects/{}", hash.get(0..2)
-                              .expect("Failed to name an asset")));
+            path.push(format!("assets/objects/{}/{}", hash_dir, hash));
             self.downloader.push_to_queue(format!(
                     "https://resources.download.minecraft.net/{}/{}", 
-                    hash.get(0..2)
-                    .expect("Failed to name asset dir"),
+                    hash_dir,
                     hash),
-                    path)
-                .expect("Failed to push asset to queue");
+                    path,
+                    Some(hash.to_string()),
+                    asset.1["size"].as_u64())?;
         }
         Ok(())
     }
 
-    fn install_client(&mut self, version_file: &VersionFile) -> Result<(), Box<dyn std::error::Error>> {
-        let mut path = PathBuf::new();
-        path.push(&self.sys.game_dir);
-        path.push("client");
-        self.downloader.push_to_queue(version_file.downloads.client.url.clone(), path)
-            .expect("Failed to push client to queue");
+    // Named after the version id like the vanilla launcher does, Forge leaves ${version_name}.jar
+    // off its module path
+    fn client_path(&self, version_file: &VersionFile) -> PathBuf {
+        let mut path = PathBuf::from(&self.sys.game_dir);
+        path.push(format!("versions/{}/{}.jar", version_file.id, version_file.id));
+        path
+    }
+
+    fn install_client(&mut self, version_file: &VersionFile) -> Result<(), LauncherError> {
+        let path = self.client_path(version_file);
+        self.downloader.push_to_queue(version_file.downloads.client.url.clone(), 
+                                      path.clone(),
+                                      Some(version_file.downloads.client.sha1.clone()),
+                                      u64::try_from(version_file.downloads.client.size).ok())?;
+        self.classpath.push(path);
+
+        Ok(())
+    }
+
+    pub fn log_config_path(&self, version_file: &VersionFile) -> Option<PathBuf> {
+        let logging = version_file.logging.as_ref()?;
+        let mut path = PathBuf::from(&self.sys.game_dir);
+        path.push("assets/log_configs");
+        path.push(&logging.client.file.id);
+        Some(path)
+    }
+
+    fn install_log_config(&mut self, version_file: &VersionFile) -> Result<(), LauncherError> {
+        let (logging, path) = match (&version_file.logging, self.log_config_path(version_file)) {
+            (Some(logging), Some(path)) => (logging, path),
+            _ => return Ok(()),
+        };
 
+        self.downloader.push_to_queue(logging.client.file.url.clone(), 
+                                      path,
+                                      Some(logging.client.file.sha1.clone()),
+                                      u64::try_from(logging.client.file.size).ok())?;
         Ok(())
     }
 
-    fn install_libraries(&mut self, version_file: &VersionFile) -> Result<(), Box<dyn std::error::Error>> {
+    fn install_libraries(&mut self, version_file: &VersionFile) -> Result<(), LauncherError> {
+        let env = Environment::current();
+        let mut libraries_dir = PathBuf::from(&self.sys.game_dir);
+        libraries_dir.push("libraries");
+
         for lib in &version_file.libraries {
-            let mut path = PathBuf::new();
-            path.push(&self.sys.game_dir);
-            match lib.rules.get(0) {
-                Some(rule) => match rule.os.name.as_str() {
-                    "linux" => path.push("natives/linux"),
-                    "osx" => path.push("natives/osx"),
-                    "windows" => path.push("natives/windows"),
-                    _ => println!("Failed to parse libraries"),
-                },
-                None => path.push("libraries"),
+            if !rules::is_allowed(&lib.rules, &env) {
+                println!("Skipping {}, not meant for this platform", lib.name);
+                continue
+            }
+
+            if let Some(artifact) = &lib.downloads.artifact {
+                let path = libraries_dir.join(&artifact.path);
+                // No URL means a loader installer provides it
+                if !artifact.url.is_empty() {
+                    self.downloader.push_to_queue(artifact.url.clone(), 
+                                                  path.clone(),
+                                                  Some(artifact.sha1.clone()),
+                                                  u64::try_from(artifact.size).ok())?;
+                }
+
+                if natives::is_native_for(lib, &env) {
+                    self.natives.push(NativeLibrary {
+                        jar: path.clone(),
+                        exclude: Vec::new(),
+                    });
+                }
+                self.classpath.push(path);
+            } else if let Some(repository) = &lib.url {
+                let coordinate = MavenCoordinate::parse(&lib.name)?;
+                let path = coordinate.local_path(&libraries_dir);
+                self.downloader.push_to_queue(coordinate.url(repository),
+                                              path.clone(),
+                                              lib.sha1.clone(),
+                                              lib.size.and_then(|size| u64::try_from(size).ok()))?;
+                self.classpath.push(path);
+            }
+
+            // Older versions keep natives in a separate classifier jar, never on the classpath
+            let native = natives::legacy_classifier(lib, &env)
+                .and_then(|classifier| lib.downloads.classifiers.as_ref()?.get(&classifier));
+            if let Some(artifact) = native {
+                let path = libraries_dir.join(&artifact.path);
+                self.downloader.push_to_queue(artifact.url.clone(), 
+                                              path.clone(),
+                                              Some(artifact.sha1.clone()),
+                                              u64::try_from(artifact.size).ok())?;
+                self.natives.push(NativeLibrary {
+                    jar: path,
+                    exclude: lib.extract
+                        .as_ref()
+                        .map(|extract| extract.exclude.clone())
+                        .unwrap_or_default(),
+                });
             }
-            self.downloader.push_to_queue(lib.downloads.artifact.url.clone(), path)
-                .expect("Failed to push library to queue");
         }
         Ok(())
     }
 
-    fn install_fabric(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let fabric_libs = fabric::get_fabric_libs();
+    fn extract_natives(&self) -> Result<(), LauncherError> {
+        let natives_dir = self.natives_dir();
+        if natives_dir.exists() {
+            std::fs::remove_dir_all(&natives_dir)?;
+        }
 
-        for lib in fabric_libs {
-            let mut path = PathBuf::from(&self.sys.game_dir);
-            path.push("libraries/fabric");
-            self.downloader.push_to_queue(lib, path)
-                .expect("Failed to download Fabric");
+        for native in &self.natives {
+            natives::extract(native, &natives_dir)?;
         }
         Ok(())
     }
 
-    fn install_mods(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let mods = mods::get_mod_list();
+    async fn install_java(&mut self, version_file: &VersionFile) -> Result<Option<JavaRuntime>, LauncherError> {
+        match &version_file.java_version {
+            Some(java_version) => {
+                let root_dir = self.root_dir().to_path_buf();
+                java::queue_runtime(&mut self.downloader, &root_dir, java_version).await
+            },
+            None => Ok(None),
+        }
+    }
+
+    async fn install_loader(&mut self, version_file: VersionFile) -> Result<VersionFile, LauncherError> {
+        let Some(loader) = self.game.pack.loader.clone() else {
+            return Ok(version_file)
+        };
+        let minecraft_version = &self.game.pack.minecraft_version;
+
+        let loader_version = loader.kind.resolve_version(&self.downloader,
+                                                         minecraft_version,
+                                                         loader.version.as_deref())
+            .await?;
+
+        let profile = match loader.kind {
+            ModLoader::Forge | ModLoader::NeoForge => {
+                let forge = ForgeInstall::fetch(loader.kind,
+                                                &self.downloader,
+                                                &self.sys.game_dir,
+                                                minecraft_version,
+                                                &loader_version)
+                    .await?;
+                forge.queue_libraries(&mut self.downloader, &self.sys.game_dir.join("libraries"))?;
+                let profile = forge.version_profile.clone();
+                self.forge = Some(forge);
+                profile
+            },
+            ModLoader::Fabric | ModLoader::Quilt => loader.kind.get_profile(&self.downloader,
+                                                                            &self.sys.game_dir,
+                                                                            minecraft_version,
+                                                                            &loader_version)
+                .await?,
+        };
+
+        if profile.inherits_from != version_file.id {
+            return Err(LauncherError::ManifestParse(format!(
+                        "{} inherits from {} instead of {}",
+                        profile.id, profile.inherits_from, version_file.id)));
+        }
+
+        Ok(version_file.merge(profile))
+    }
+
+    // The pack's mods and what its Modrinth entries resolve to
+    // Only the mods for the side being installed
+    async fn resolve_mods(&self) -> Result<Vec<PackMod>, LauncherError> {
+        let side = self.game.side;
+        let mut mods: Vec<PackMod> = self.game.pack.mods
+            .iter()
+            .filter(|modif| modif.side.runs_on(side))
+            .cloned()
+            .collect();
+
+        // Filtered before resolving too, so that dependencies of the other side's mods stay out
+        let modrinth: Vec<_> = self.game.pack.modrinth
+            .iter()
+            .filter(|modif| modif.side.runs_on(side))
+            .cloned()
+            .collect();
+        if !modrinth.is_empty() {
+            let resolved = ModrinthApi::new()?
+                .resolve(&modrinth,
+                         &self.game.pack.minecraft_version,
+                         self.game.pack.loader.as_ref().map(|loader| loader.kind))
+                .await?;
+            mods.extend(resolved.into_iter().filter(|modif| modif.side.runs_on(side)));
+        }
+        Ok(mods)
+    }
+
+    // Returns the file names in mods/
+    // Disabled optional mods are kept as <name>.disabled
+    fn install_mods(&mut self, mods: Vec<PackMod>) -> Result<Vec<String>, LauncherError> {
+        let mods_dir = self.sys.instance_dir.join("mods");
+        let optional_mods = OptionalMods::load(&self.sys.instance_dir)?;
+        let mut files = Vec::new();
 
         for modif in mods {
-            let mut path = PathBuf::from(&self.sys.game_dir);
-            path.push("mods");
-            self.downloader.push_to_queue(modif, path)
-                .expect("Failed to download mods");
+            let mut name = modif.local_name()?;
+            if modif.optional {
+                let enabled = optional_mods.is_enabled(&modif.key()?, modif.default_enabled);
+                name = optional_mods::apply(&mods_dir, &name, enabled)?;
+            }
+            self.downloader.push_to_queue(modif.url, mods_dir.join(&name), Some(modif.sha1), Some(modif.size))?;
+            files.push(name);
         }
+
+        Ok(files)
+    }
+
+    fn update_managed_mods(&self, files: Vec<String>) -> Result<(), LauncherError> {
+        let mods_dir = self.sys.instance_dir.join("mods");
+        ManagedMods::load(&self.sys.instance_dir)?.remove_stale(&mods_dir, &files)?;
+        ManagedMods { files }.save(&self.sys.instance_dir)?;
         Ok(())
     }
 }
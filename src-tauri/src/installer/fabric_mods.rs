@@ -0,0 +1,313 @@
+// Reads the fabric.mod.json of every jar in mods/, nested jars included, to catch what
+// would make Fabric refuse to start before launching : jars that aren't Fabric mods,
+// the same mod twice, missing dependencies and mods declaring others broken
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Cursor, Read, Seek},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::LauncherError,
+    version_range,
+};
+
+const FABRIC_MOD_JSON: &str = "fabric.mod.json";
+
+#[derive(Debug, Deserialize)]
+struct FabricModJson {
+    id: String,
+    version: String,
+    #[serde(default)]
+    environment: Option<String>,
+    #[serde(default)]
+    depends: HashMap<String, VersionRanges>,
+    #[serde(default)]
+    breaks: HashMap<String, VersionRanges>,
+    #[serde(default)]
+    jars: Vec<NestedJar>,
+    #[serde(default)]
+    provides: Vec<String>,
+}
+
+// A list means any of them
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum VersionRanges {
+    One(String),
+    Any(Vec<String>),
+}
+
+#[derive(Debug, Deserialize)]
+struct NestedJar {
+    file: String,
+}
+
+impl VersionRanges {
+    fn matches(&self, version: &str) -> bool {
+        match self {
+            VersionRanges::One(range) => version_range::matches(range, version),
+            VersionRanges::Any(ranges) => ranges.iter().any(|range| version_range::matches(range, version)),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            VersionRanges::One(range) => range.clone(),
+            VersionRanges::Any(ranges) => ranges.join(" || "),
+        }
+    }
+}
+
+struct FabricMod {
+    // The jar in mods/ it comes from
+    file: String,
+    nested: bool,
+    json: FabricModJson,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ModIssue {
+    NotFabric { file: String },
+    Unreadable { file: String, reason: String },
+    ServerOnly { file: String, id: String },
+    DuplicateId { id: String, files: Vec<String> },
+    MissingDependency { file: String, id: String, dependency: String, range: String },
+    Breaks { file: String, id: String, broken: String, range: String, version: String },
+}
+
+impl std::fmt::Display for ModIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModIssue::NotFabric { file } => write!(f, "{} n'est pas un mod Fabric", file),
+            ModIssue::Unreadable { file, reason } => write!(f, "{} est illisible : {}", file, reason),
+            ModIssue::ServerOnly { file, id } => write!(f, "{} ({}) est un mod serveur", id, file),
+            ModIssue::DuplicateId { id, files } => write!(f, "{} est présent plusieurs fois : {}", id, files.join(", ")),
+            ModIssue::MissingDependency { id, dependency, range, .. } => write!(f, "{} a besoin de {} {}", id, dependency, range),
+            ModIssue::Breaks { id, broken, version, .. } => write!(f, "{} est incompatible avec {} {}", id, broken, version),
+        }
+    }
+}
+
+fn read_mods<R: Read + Seek>(archive: &mut zip::ZipArchive<R>, file: &str, nested: bool, mods: &mut Vec<FabricMod>, issues: &mut Vec<ModIssue>) -> Result<bool, LauncherError> {
+    let json = match archive.by_name(FABRIC_MOD_JSON) {
+        Ok(mut entry) => {
+            let mut content = String::new();
+            entry.read_to_string(&mut content)?;
+            content
+        },
+        Err(_) => return Ok(false),
+    };
+    let json: FabricModJson = serde_json::from_str(&json)?;
+
+    // A broken nested jar is reported on its own, the mod around it is still checked
+    for jar in &json.jars {
+        let read = read_nested(archive, &jar.file)
+            .and_then(|mut nested_archive| read_mods(&mut nested_archive, file, true, mods, issues));
+        if let Err(err) = read {
+            issues.push(ModIssue::Unreadable { file: format!("{}!{}", file, jar.file), reason: err.to_string() });
+        }
+    }
+
+    mods.push(FabricMod {
+        file: file.to_string(),
+        nested,
+        json,
+    });
+    Ok(true)
+}
+
+fn read_nested<R: Read + Seek>(archive: &mut zip::ZipArchive<R>, path: &str) -> Result<zip::ZipArchive<Cursor<Vec<u8>>>, LauncherError> {
+    let mut content = Vec::new();
+    archive.by_name(path)?.read_to_end(&mut content)?;
+    Ok(zip::ZipArchive::new(Cursor::new(content))?)
+}
+
+// `provided` holds what the game itself provides, like minecraft, java and fabricloader
+pub fn check(mods_dir: &Path, provided: &[(String, String)]) -> Result<Vec<ModIssue>, LauncherError> {
+    let mut issues = Vec::new();
+    let mut mods = Vec::new();
+
+    if mods_dir.is_dir() {
+        let mut entries = fs::read_dir(mods_dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "jar"))
+            .collect::<Vec<_>>();
+        entries.sort();
+
+        for path in entries {
+            let file = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            let read = zip::ZipArchive::new(fs::File::open(&path)?)
+                .map_err(LauncherError::from)
+                .and_then(|mut archive| read_mods(&mut archive, &file, false, &mut mods, &mut issues));
+            match read {
+                Ok(true) => {},
+                Ok(false) => issues.push(ModIssue::NotFabric { file }),
+                Err(err) => issues.push(ModIssue::Unreadable { file, reason: err.to_string() }),
+            }
+        }
+    }
+
+    let mut by_id: HashMap<&str, Vec<&FabricMod>> = HashMap::new();
+    for modif in mods.iter().filter(|modif| !modif.nested) {
+        by_id.entry(&modif.json.id).or_default().push(modif);
+        if modif.json.environment.as_deref() == Some("server") {
+            issues.push(ModIssue::ServerOnly { file: modif.file.clone(), id: modif.json.id.clone() });
+        }
+    }
+    let mut duplicates: Vec<_> = by_id.into_iter().filter(|(_, mods)| mods.len() > 1).collect();
+    duplicates.sort_by_key(|(id, _)| *id);
+    for (id, mods) in duplicates {
+        issues.push(ModIssue::DuplicateId {
+            id: id.to_string(),
+            files: mods.iter().map(|modif| modif.file.clone()).collect(),
+        });
+    }
+
+    // Every id with the versions it's present in, nested jars and provides included
+    let mut versions: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (id, version) in provided {
+        versions.entry(id).or_default().push(version);
+    }
+    for modif in &mods {
+        versions.entry(&modif.json.id).or_default().push(&modif.json.version);
+        for id in &modif.json.provides {
+            versions.entry(id).or_default().push(&modif.json.version);
+        }
+    }
+
+    for modif in mods.iter().filter(|modif| !modif.nested) {
+        for (dependency, ranges) in &modif.json.depends {
+            let satisfied = versions
+                .get(dependency.as_str())
+                .is_some_and(|versions| versions.iter().any(|version| ranges.matches(version)));
+            if !satisfied {
+                issues.push(ModIssue::MissingDependency {
+                    file: modif.file.clone(),
+                    id: modif.json.id.clone(),
+                    dependency: dependency.clone(),
+                    range: ranges.describe(),
+                });
+            }
+        }
+
+        for (broken, ranges) in &modif.json.breaks {
+            let present = versions
+                .get(broken.as_str())
+                .and_then(|versions| versions.iter().find(|version| ranges.matches(version)));
+            if let Some(version) = present {
+                issues.push(ModIssue::Breaks {
+                    file: modif.file.clone(),
+                    id: modif.json.id.clone(),
+                    broken: broken.clone(),
+                    range: ranges.describe(),
+                    version: version.to_string(),
+                });
+            }
+        }
+    }
+
+    Ok(issues)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use crate::test_dir::TempDir;
+
+    use super::*;
+
+    fn jar(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in entries {
+            zip.start_file(*name, zip::write::FileOptions::default()).unwrap();
+            zip.write_all(content).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn fabric_mod(json: &str) -> Vec<u8> {
+        jar(&[(FABRIC_MOD_JSON, json.as_bytes())])
+    }
+
+    // Checks a mods/ holding these jars, on Minecraft 1.20.1
+    fn check_jars(jars: &[(&str, Vec<u8>)]) -> Vec<ModIssue> {
+        let mods_dir = TempDir::new("fabric-mods");
+        for (name, content) in jars {
+            fs::write(mods_dir.join(name), content).unwrap();
+        }
+        check(&mods_dir, &[("minecraft".to_string(), "1.20.1".to_string())]).unwrap()
+    }
+
+    #[test]
+    fn broken_nested_jar_keeps_the_outer_mod() {
+        let library = jar(&[(FABRIC_MOD_JSON, br#"{"id": "library", "version": "1.0.0"}"#)]);
+        let outer = jar(&[
+            (FABRIC_MOD_JSON, br#"{
+                "id": "outer",
+                "version": "2.0.0",
+                "depends": {"library": ">=1.0.0", "minecraft": "1.20.1"},
+                "jars": [{"file": "META-INF/jars/library.jar"}, {"file": "META-INF/jars/broken.jar"}]
+            }"#),
+            ("META-INF/jars/library.jar", &library),
+            ("META-INF/jars/broken.jar", b"not a zip"),
+        ]);
+        let needing_outer = fabric_mod(r#"{"id": "addon", "version": "1.0.0", "depends": {"outer": "2.x"}}"#);
+
+        let issues = check_jars(&[("outer.jar", outer), ("addon.jar", needing_outer)]);
+        assert!(matches!(issues.as_slice(),
+                         [ModIssue::Unreadable { file, .. }] if file == "outer.jar!META-INF/jars/broken.jar"),
+                "{:?}", issues);
+    }
+
+    #[test]
+    fn reports_jars_that_arent_fabric_mods() {
+        let forge = jar(&[("META-INF/mods.toml", b"modLoader=\"javafml\"")]);
+        let issues = check_jars(&[("Clumps-forge-1.19.2-9.0.0+14.jar", forge)]);
+        assert!(matches!(issues.as_slice(),
+                         [ModIssue::NotFabric { file }] if file == "Clumps-forge-1.19.2-9.0.0+14.jar"),
+                "{:?}", issues);
+    }
+
+    #[test]
+    fn reports_duplicate_ids() {
+        let issues = check_jars(&[
+            ("sodium-0.4.4.jar", fabric_mod(r#"{"id": "sodium", "version": "0.4.4"}"#)),
+            ("sodium-0.4.10.jar", fabric_mod(r#"{"id": "sodium", "version": "0.4.10"}"#)),
+        ]);
+        assert!(matches!(issues.as_slice(),
+                         [ModIssue::DuplicateId { id, files }] if id == "sodium" && files == &["sodium-0.4.10.jar", "sodium-0.4.4.jar"]),
+                "{:?}", issues);
+    }
+
+    #[test]
+    fn reports_missing_dependencies() {
+        let issues = check_jars(&[
+            ("indium.jar", fabric_mod(r#"{"id": "indium", "version": "1.0.9", "depends": {"sodium": ">=0.4.10", "minecraft": "1.20.x"}}"#)),
+            ("sodium.jar", fabric_mod(r#"{"id": "sodium", "version": "0.4.4"}"#)),
+        ]);
+        assert!(matches!(issues.as_slice(),
+                         [ModIssue::MissingDependency { id, dependency, range, .. }]
+                         if id == "indium" && dependency == "sodium" && range == ">=0.4.10"),
+                "{:?}", issues);
+    }
+
+    #[test]
+    fn reports_declared_breaks() {
+        let issues = check_jars(&[
+            ("iris.jar", fabric_mod(r#"{"id": "iris", "version": "1.6.4", "breaks": {"optifabric": "*"}}"#)),
+            ("optifabric.jar", fabric_mod(r#"{"id": "optifabric", "version": "1.13.0"}"#)),
+        ]);
+        assert!(matches!(issues.as_slice(),
+                         [ModIssue::Breaks { id, broken, version, .. }]
+                         if id == "iris" && broken == "optifabric" && version == "1.13.0"),
+                "{:?}", issues);
+    }
+}
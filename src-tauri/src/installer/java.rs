@@ -0,0 +1,195 @@
+// Java runtimes from Mojang's java-runtime manifest, installed under runtime/<component>
+// like the vanilla launcher does so players don't need the right Java on their own
+
+use std::{
+    collections::HashMap,
+    env,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    downloader::Downloader,
+    error::LauncherError,
+};
+
+use super::{
+    rules::Environment,
+    version_file::JavaVersion,
+};
+
+const JAVA_RUNTIMES: &str = "https://launchermeta.mojang.com/v1/products/java-runtime/2ec0cc96c44e5a76b9c8b7c39df7210883d12871/all.json";
+
+#[derive(Debug, Deserialize)]
+struct RuntimeEntry {
+    manifest: RuntimeManifestLink,
+}
+
+#[derive(Debug, Deserialize)]
+struct RuntimeManifestLink {
+    url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RuntimeManifest {
+    files: HashMap<String, RuntimeFile>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum RuntimeFile {
+    Directory,
+    File {
+        downloads: RuntimeDownloads,
+        #[serde(default)]
+        executable: bool,
+    },
+    Link {
+        target: String,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct RuntimeDownloads {
+    raw: RuntimeDownload,
+}
+
+#[derive(Debug, Deserialize)]
+struct RuntimeDownload {
+    sha1: String,
+    size: u64,
+    url: String,
+}
+
+// Paths are relative to runtime/<component>, so that lockfiles can hold it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JavaRuntime {
+    component: String,
+    executables: Vec<String>,
+    links: Vec<(String, String)>,
+}
+
+// Key of the current platform in the runtime list
+fn platform(env: &Environment) -> Option<&'static str> {
+    match (env.os_name.as_str(), env.arch.as_str()) {
+        ("linux", "x86_64") => Some("linux"),
+        ("linux", "x86") => Some("linux-i386"),
+        ("osx", "arm64") => Some("mac-os-arm64"),
+        ("osx", _) => Some("mac-os"),
+        ("windows", "x86") => Some("windows-x86"),
+        ("windows", "arm64") => Some("windows-arm64"),
+        ("windows", _) => Some("windows-x64"),
+        _ => None,
+    }
+}
+
+// JAVA_HOME, then whatever "java" is on the PATH
+pub fn system_java() -> PathBuf {
+    env::var_os("JAVA_HOME")
+        .map(|home| Path::new(&home).join("bin").join(java_binary()))
+        .filter(|java| java.is_file())
+        .unwrap_or_else(|| PathBuf::from("java"))
+}
+
+fn java_binary() -> &'static str {
+    match env::consts::OS {
+        "windows" => "java.exe",
+        _ => "java",
+    }
+}
+
+// Queues the runtime files, None when Mojang has no runtime for this platform
+pub async fn queue_runtime(downloader: &mut Downloader, game_dir: &Path, java_version: &JavaVersion) -> Result<Option<JavaRuntime>, LauncherError> {
+    let Some(platform) = platform(&Environment::current()) else {
+        return Ok(None)
+    };
+
+    let runtimes: HashMap<String, HashMap<String, Vec<RuntimeEntry>>> = serde_json::from_value(
+        downloader.to_json(JAVA_RUNTIMES.to_string()).await?)?;
+
+    let entry = runtimes
+        .get(platform)
+        .and_then(|components| components.get(&java_version.component))
+        .and_then(|entries| entries.first());
+    let Some(entry) = entry else {
+        println!("No {} runtime for {}, using the system Java", java_version.component, platform);
+        return Ok(None)
+    };
+
+    let manifest: RuntimeManifest = serde_json::from_value(
+        downloader.to_json(entry.manifest.url.clone()).await?)?;
+
+    let mut runtime = JavaRuntime {
+        component: java_version.component.clone(),
+        executables: Vec::new(),
+        links: Vec::new(),
+    };
+    let home = runtime.home(game_dir);
+
+    for (name, file) in manifest.files {
+        let path = home.join(&name);
+        match file {
+            RuntimeFile::Directory => fs::create_dir_all(&path)?,
+            RuntimeFile::File { downloads, executable } => {
+                downloader.push_to_queue(downloads.raw.url,
+                                         path.clone(),
+                                         Some(downloads.raw.sha1),
+                                         Some(downloads.raw.size))?;
+                if executable {
+                    runtime.executables.push(name);
+                }
+            },
+            RuntimeFile::Link { target } => runtime.links.push((name, target)),
+        }
+    }
+
+    Ok(Some(runtime))
+}
+
+impl JavaRuntime {
+    fn home(&self, game_dir: &Path) -> PathBuf {
+        let mut home = game_dir.to_path_buf();
+        home.push("runtime");
+        home.push(&self.component);
+        home
+    }
+
+    pub fn java(&self, game_dir: &Path) -> PathBuf {
+        let home = self.home(game_dir);
+        let bin = match env::consts::OS {
+            "macos" => home.join("jre.bundle/Contents/Home/bin"),
+            _ => home.join("bin"),
+        };
+        bin.join(java_binary())
+    }
+
+    // Downloads lose the executable bit and links aren't files, both are restored once the queue is done
+    #[cfg(unix)]
+    pub fn finish(&self, game_dir: &Path) -> Result<(), LauncherError> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let home = self.home(game_dir);
+        for name in &self.executables {
+            fs::set_permissions(home.join(name), fs::Permissions::from_mode(0o755))?;
+        }
+
+        for (name, target) in &self.links {
+            let path = home.join(name);
+            if fs::symlink_metadata(&path).is_ok() {
+                continue
+            }
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            std::os::unix::fs::symlink(target, &path)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    pub fn finish(&self, _game_dir: &Path) -> Result<(), LauncherError> {
+        Ok(())
+    }
+}
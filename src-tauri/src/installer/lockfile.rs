@@ -0,0 +1,309 @@
+// Everything an install resolved, written next to the instance after each install. Installing
+// from it asks nothing to Mojang, the loaders or Modrinth, so everyone gets the same files.
+// Paths are relative to the game directory, or the server directory for servers. Natives, libraries with rules and the runtime
+// differ between platforms, they go in one section per "os-arch" that installing adds to
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+
+use crate::{
+    downloader::{self, Download, Downloader},
+    error::LauncherError,
+    maven::MavenCoordinate,
+    modpack::relative_path,
+    pack::InstallSide,
+};
+
+use super::{
+    java::JavaRuntime,
+    natives::NativeLibrary,
+    rules::Environment,
+    version_file::VersionFile,
+};
+
+pub const LOCKFILE: &str = "pack.lock.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Lockfile {
+    pub pack: String,
+    pub pack_version: String,
+    // A server install doesn't get the same mods
+    #[serde(default)]
+    pub side: InstallSide,
+    // Merged with the loader profile, what the launcher builds its arguments from
+    pub version_file: VersionFile,
+    // Forge and NeoForge still have to run their processors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forge_installer: Option<String>,
+    // File names in mods/, for the managed mods. Always the enabled name, what players
+    // chose for the optional ones is applied when installing
+    pub mods: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub optional_mods: Vec<LockedOptionalMod>,
+    // The ones every platform downloads
+    pub files: Vec<LockedFile>,
+    pub platforms: BTreeMap<String, LockedPlatform>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockedPlatform {
+    pub classpath: Vec<String>,
+    pub natives: Vec<LockedNative>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub java: Option<JavaRuntime>,
+    pub files: Vec<LockedFile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockedOptionalMod {
+    pub file: String,
+    pub key: String,
+    pub default_enabled: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockedFile {
+    pub url: String,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha1: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockedNative {
+    pub jar: String,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+pub fn relative(game_dir: &Path, path: &Path) -> Result<String, LauncherError> {
+    let relative = path.strip_prefix(game_dir)
+        .map_err(|_| LauncherError::ManifestParse(format!("{} is outside of the game directory", path.display())))?;
+    Ok(relative.to_string_lossy().replace('\\', "/"))
+}
+
+// Lockfiles can come from the network, nothing may point outside the game directory
+pub fn absolute(game_dir: &Path, path: &str) -> Result<PathBuf, LauncherError> {
+    Ok(game_dir.join(relative_path(path)?))
+}
+
+pub fn platform_key(env: &Environment) -> String {
+    format!("{}-{}", env.os_name, env.arch)
+}
+
+// Files that depend on the platform : the Java runtime, and libraries restricted by rules or
+// carrying natives, whichever platform they're for
+pub fn split_files(version_file: &VersionFile, files: Vec<LockedFile>) -> Result<(Vec<LockedFile>, Vec<LockedFile>), LauncherError> {
+    let mut paths = HashSet::new();
+    for lib in &version_file.libraries {
+        let classifier = lib.name.split(':').nth(3).unwrap_or("");
+        if lib.rules.is_empty() && lib.natives.is_none() && !classifier.starts_with("natives-") {
+            continue
+        }
+        if let Some(artifact) = &lib.downloads.artifact {
+            paths.insert(format!("libraries/{}", artifact.path));
+        } else if lib.url.is_some() {
+            let local = MavenCoordinate::parse(&lib.name)?.local_path(Path::new("libraries"));
+            paths.insert(local.to_string_lossy().replace('\\', "/"));
+        }
+        for artifact in lib.downloads.classifiers.iter().flat_map(|classifiers| classifiers.values()) {
+            paths.insert(format!("libraries/{}", artifact.path));
+        }
+    }
+    Ok(files
+        .into_iter()
+        .partition(|file| !file.path.starts_with("runtime/") && !paths.contains(&file.path)))
+}
+
+impl LockedFile {
+    pub fn new(game_dir: &Path, download: &Download) -> Result<Self, LauncherError> {
+        Ok(Self {
+            url: download.url.clone(),
+            path: relative(game_dir, &download.path)?,
+            sha1: download.sha1.clone(),
+            size: download.size,
+        })
+    }
+}
+
+impl LockedNative {
+    pub fn new(game_dir: &Path, native: &NativeLibrary) -> Result<Self, LauncherError> {
+        Ok(Self {
+            jar: relative(game_dir, &native.jar)?,
+            exclude: native.exclude.clone(),
+        })
+    }
+
+    pub fn to_native(&self, game_dir: &Path) -> Result<NativeLibrary, LauncherError> {
+        Ok(NativeLibrary {
+            jar: absolute(game_dir, &self.jar)?,
+            exclude: self.exclude.clone(),
+        })
+    }
+}
+
+impl Lockfile {
+    // Published lockfiles are pinned by the pack manifest with their sha1
+    pub async fn fetch(downloader: &Downloader, url: &str, sha1: &str) -> Result<Self, LauncherError> {
+        let lockfile = downloader.to_text(url.to_string()).await?;
+        let hash = format!("{:x}", Sha1::digest(lockfile.as_bytes()));
+        if !hash.eq_ignore_ascii_case(sha1) {
+            return Err(LauncherError::Checksum(PathBuf::from(url)));
+        }
+        Ok(serde_json::from_str(&lockfile)?)
+    }
+
+    // Loader meta libraries and Fabric's server launcher come without a hash, once downloaded
+    // they're pinned to what was fetched so that installing from the lockfile gets the same bytes
+    pub fn pin_downloaded(&mut self, root_dir: &Path) -> Result<(), LauncherError> {
+        let platform_files = self.platforms.values_mut().flat_map(|platform| platform.files.iter_mut());
+        for file in self.files.iter_mut().chain(platform_files).filter(|file| file.sha1.is_none() || file.size.is_none()) {
+            let path = absolute(root_dir, &file.path)?;
+            file.sha1 = Some(downloader::file_sha1(&path)?);
+            file.size = Some(fs::metadata(&path)?.len());
+        }
+        Ok(())
+    }
+
+    // Keeps the other platforms of the lockfile already there, as long as they locked the same pack
+    pub fn save(&self, instance_dir: &Path) -> Result<(), LauncherError> {
+        let path = instance_dir.join(LOCKFILE);
+        let mut lockfile = self.clone();
+        let previous = fs::read_to_string(&path)
+            .ok()
+            .and_then(|previous| serde_json::from_str::<Lockfile>(&previous).ok());
+        if let Some(previous) = previous {
+            if previous.same_pack(self) {
+                for (platform, locked) in previous.platforms {
+                    lockfile.platforms.entry(platform).or_insert(locked);
+                }
+            } else {
+                println!("The pack changed, dropping the other platforms from the lockfile");
+            }
+        }
+        fs::write(path, serde_json::to_string_pretty(&lockfile)?)?;
+        Ok(())
+    }
+
+    fn same_pack(&self, other: &Lockfile) -> bool {
+        self.pack == other.pack
+            && self.pack_version == other.pack_version
+            && self.side == other.side
+            && self.forge_installer == other.forge_installer
+            && self.mods == other.mods
+            && self.optional_mods == other.optional_mods
+            && self.files == other.files
+    }
+
+    pub fn platform(&self, env: &Environment, side: InstallSide) -> Result<&LockedPlatform, LauncherError> {
+        if self.side != side {
+            return Err(LauncherError::ManifestParse(format!(
+                        "The lockfile was made for a {:?} install, not a {:?} one", self.side, side)));
+        }
+        let key = platform_key(env);
+        self.platforms
+            .get(&key)
+            .ok_or_else(|| LauncherError::ManifestParse(format!(
+                        "The lockfile has nothing for {}, install the pack once on it to add it", key)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_dir::TempDir;
+
+    use super::*;
+
+    fn locked_file(path: &str) -> LockedFile {
+        LockedFile {
+            url: format!("https://example.com/{}", path),
+            path: path.to_string(),
+            sha1: None,
+            size: None,
+        }
+    }
+
+    fn lockfile(platform: &str, mods: Vec<String>) -> Lockfile {
+        Lockfile {
+            pack: "PBLM".into(),
+            pack_version: "1".into(),
+            side: InstallSide::Client,
+            version_file: serde_json::from_str(include_str!("../../tests/fixtures/version_1.20.1.json")).unwrap(),
+            forge_installer: None,
+            mods,
+            optional_mods: Vec::new(),
+            files: vec![locked_file("versions/1.20.1/1.20.1.jar")],
+            platforms: BTreeMap::from([(platform.to_string(), LockedPlatform {
+                classpath: Vec::new(),
+                natives: Vec::new(),
+                java: None,
+                files: Vec::new(),
+            })]),
+        }
+    }
+
+    #[test]
+    fn splits_platform_files() {
+        let version_file: VersionFile = serde_json::from_str(include_str!("../../tests/fixtures/version_1.20.1.json")).unwrap();
+        let files = vec![
+            locked_file("libraries/com/mojang/logging/1.1.1/logging-1.1.1.jar"),
+            locked_file("libraries/org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1-natives-linux.jar"),
+            locked_file("runtime/java-runtime-gamma/bin/java"),
+            locked_file("mods/sodium.jar"),
+        ];
+
+        let (common, platform) = split_files(&version_file, files).unwrap();
+        let paths = |files: &[LockedFile]| files.iter().map(|file| file.path.clone()).collect::<Vec<_>>();
+        assert_eq!(paths(&common), ["libraries/com/mojang/logging/1.1.1/logging-1.1.1.jar", "mods/sodium.jar"]);
+        assert_eq!(paths(&platform), [
+            "libraries/org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1-natives-linux.jar",
+            "runtime/java-runtime-gamma/bin/java",
+        ]);
+    }
+
+    #[test]
+    fn pins_unhashed_files() {
+        let game_dir = TempDir::new("lockfile");
+        fs::create_dir_all(game_dir.join("libraries/net/fabricmc/fabric-loader/0.14.21")).unwrap();
+        fs::write(game_dir.join("libraries/net/fabricmc/fabric-loader/0.14.21/fabric-loader-0.14.21.jar"), "loader").unwrap();
+        fs::create_dir_all(game_dir.join("versions/1.20.1")).unwrap();
+        fs::write(game_dir.join("versions/1.20.1/1.20.1.jar"), "").unwrap();
+
+        let mut lockfile = lockfile("linux-x86_64", Vec::new());
+        lockfile.platforms.get_mut("linux-x86_64").unwrap().files
+            .push(locked_file("libraries/net/fabricmc/fabric-loader/0.14.21/fabric-loader-0.14.21.jar"));
+        lockfile.pin_downloaded(&game_dir).unwrap();
+
+        let files: Vec<_> = lockfile.files.iter().chain(&lockfile.platforms["linux-x86_64"].files).collect();
+        assert!(files.iter().all(|file| file.sha1.is_some() && file.size.is_some()));
+        assert_eq!(files[0].sha1.as_deref(), Some("da39a3ee5e6b4b0d3255bfef95601890afd80709"));
+        assert_eq!(files[1].size, Some(6));
+    }
+
+    #[test]
+    fn save_keeps_other_platforms() {
+        let dir = TempDir::new("lockfile");
+
+        lockfile("linux-x86_64", vec!["sodium.jar".into()]).save(&dir).unwrap();
+        lockfile("windows-x86_64", vec!["sodium.jar".into()]).save(&dir).unwrap();
+        let saved: Lockfile = serde_json::from_str(&fs::read_to_string(dir.join(LOCKFILE)).unwrap()).unwrap();
+        assert_eq!(saved.platforms.keys().collect::<Vec<_>>(), ["linux-x86_64", "windows-x86_64"]);
+
+        // Another set of mods, what the other platforms locked is stale
+        lockfile("osx-arm64", vec!["iris.jar".into()]).save(&dir).unwrap();
+        let saved: Lockfile = serde_json::from_str(&fs::read_to_string(dir.join(LOCKFILE)).unwrap()).unwrap();
+        assert_eq!(saved.platforms.keys().collect::<Vec<_>>(), ["osx-arm64"]);
+    }
+}
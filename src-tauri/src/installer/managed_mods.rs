@@ -0,0 +1,100 @@
+// The files of mods/ the launcher put there, so that a mod leaving the pack or changing
+// version gets deleted while the ones players add themselves are left alone
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::LauncherError;
+
+const MANAGED_MODS: &str = "managed_mods.json";
+// What the launcher installed before it kept track, named after the percent-encoded end
+// of their URL. The pack now names them from the API, decoded, so they'd be there twice
+const LEGACY_MODS: [&str; 19] = [
+    "fabric-api-0.76.0%2B1.19.2.jar",
+    "voicechat-fabric-1.19.2-2.4.16.jar",
+    "phosphor-fabric-mc1.19.x-0.8.1.jar",
+    "lithium-fabric-mc1.19.2-0.11.1.jar",
+    "iris-mc1.19.2-1.6.4.jar",
+    "sodium-fabric-mc1.19.2-0.4.4%2Bbuild.18.jar",
+    "create-fabric-0.5.1-b-build.1089%2Bmc1.19.2.jar",
+    "appliedenergistics2-fabric-12.9.6.jar",
+    "XaerosWorldMap_1.31.0_Fabric_1.19.1.jar",
+    "Xaeros_Minimap_23.6.0_Fabric_1.19.1.jar",
+    "indium-1.0.9%2Bmc1.19.2.jar",
+    "jei-1.19.2-fabric-11.6.0.1016.jar",
+    "lazydfu-0.1.3.jar",
+    "ferritecore-5.0.3-fabric.jar",
+    "Clumps-forge-1.19.2-9.0.0%2B14.jar",
+    "cc-tweaked-1.19.2-1.101.3.jar",
+    "numismatic-overhaul-0.2.9%2B1.19.jar",
+    "owo-lib-0.9.3%2B1.19.jar",
+    "modmenu-4.2.0-beta.2.jar",
+];
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ManagedMods {
+    pub files: Vec<String>,
+}
+
+impl ManagedMods {
+    fn path(instance_dir: &Path) -> PathBuf {
+        instance_dir.join(MANAGED_MODS)
+    }
+
+    // Without a state file, only what older launchers installed is managed
+    pub fn load(instance_dir: &Path) -> Result<Self, LauncherError> {
+        let path = Self::path(instance_dir);
+        if !path.is_file() {
+            return Ok(Self { files: LEGACY_MODS.iter().map(|file| file.to_string()).collect() })
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, instance_dir: &Path) -> Result<(), LauncherError> {
+        fs::write(Self::path(instance_dir), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    // Deletes what was managed but isn't part of `current` anymore
+    pub fn remove_stale(&self, mods_dir: &Path, current: &[String]) -> Result<(), LauncherError> {
+        for file in self.files.iter().filter(|file| !current.contains(file)) {
+            let path = mods_dir.join(file);
+            if path.is_file() {
+                fs::remove_file(&path)?;
+                println!("Removed {}, no longer in the pack", file);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_dir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn upgrades_remove_legacy_mods() {
+        let instance_dir = TempDir::new("managed-mods");
+        let mods_dir = instance_dir.join("mods");
+        fs::create_dir_all(&mods_dir).unwrap();
+        for file in ["sodium-fabric-mc1.19.2-0.4.4%2Bbuild.18.jar", "sodium-fabric-mc1.19.2-0.4.4+build.18.jar", "mine.jar"] {
+            fs::write(mods_dir.join(file), "").unwrap();
+        }
+
+        let current = vec!["sodium-fabric-mc1.19.2-0.4.4+build.18.jar".to_string()];
+        ManagedMods::load(&instance_dir).unwrap().remove_stale(&mods_dir, &current).unwrap();
+        assert!(!mods_dir.join("sodium-fabric-mc1.19.2-0.4.4%2Bbuild.18.jar").exists());
+        assert!(mods_dir.join("sodium-fabric-mc1.19.2-0.4.4+build.18.jar").exists());
+        assert!(mods_dir.join("mine.jar").exists());
+
+        // Once saved, the state file is all that counts
+        ManagedMods { files: current }.save(&instance_dir).unwrap();
+        assert_eq!(ManagedMods::load(&instance_dir).unwrap().files, ["sodium-fabric-mc1.19.2-0.4.4+build.18.jar"]);
+    }
+}
@@ -0,0 +1,86 @@
+// Native binaries (.so, .dll, .dylib) shipped inside classifier jars, extracted
+// next to each other in a per-version directory used as java.library.path
+
+use std::{
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::error::LauncherError;
+
+use super::{
+    rules::Environment,
+    version_file::Library,
+};
+
+const NATIVE_EXTENSIONS: [&str; 4] = ["so", "dll", "dylib", "jnilib"];
+
+pub struct NativeLibrary {
+    pub jar: PathBuf,
+    pub exclude: Vec<String>,
+}
+
+// Classifier of the natives jar this platform needs, for versions using the "natives" map
+pub fn legacy_classifier(lib: &Library, env: &Environment) -> Option<String> {
+    let classifier = lib.natives.as_ref()?.get(&env.os_name)?;
+    let arch = match env.arch.as_str() {
+        "x86" => "32",
+        _ => "64",
+    };
+    Some(classifier.replace("${arch}", arch))
+}
+
+// Newer versions ship natives as regular libraries classified "natives-<os>[-<arch>]",
+// the x86_64 build being the one without an arch suffix
+pub fn is_native_for(lib: &Library, env: &Environment) -> bool {
+    let classifier = match lib.name.split(':').nth(3) {
+        Some(classifier) => classifier,
+        None => return false,
+    };
+
+    let os_name = match env.os_name.as_str() {
+        "osx" => "macos",
+        os_name => os_name,
+    };
+
+    match classifier.strip_prefix(&format!("natives-{}", os_name)) {
+        Some("") => env.arch == "x86_64",
+        Some(arch) => arch.strip_prefix('-') == Some(env.arch.as_str()),
+        None => false,
+    }
+}
+
+pub fn extract(native: &NativeLibrary, natives_dir: &Path) -> Result<(), LauncherError> {
+    let file = fs::File::open(&native.jar)?;
+    let mut archive = zip::ZipArchive::new(file)?;
+    fs::create_dir_all(natives_dir)?;
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        if entry.is_dir() {
+            continue
+        }
+
+        let name = entry.name().to_string();
+        if name.starts_with("META-INF/") || native.exclude.iter().any(|exclude| name.starts_with(exclude.as_str())) {
+            continue
+        }
+
+        // Flattening, LWJGL looks for the binaries directly in java.library.path
+        let path = Path::new(&name);
+        let is_native = path
+            .extension()
+            .is_some_and(|ext| NATIVE_EXTENSIONS.contains(&ext.to_string_lossy().as_ref()));
+        let file_name = match path.file_name() {
+            Some(file_name) if is_native => file_name,
+            _ => continue,
+        };
+
+        let mut output = fs::File::create(natives_dir.join(file_name))?;
+        io::copy(&mut entry, &mut output)?;
+    }
+
+    println!("Extracted natives from {}", native.jar.display());
+    Ok(())
+}
@@ -0,0 +1,102 @@
+// What players chose for the optional mods of an instance. A disabled mod stays in mods/
+// as <name>.disabled, which the loaders skip, so turning it back on needs no download
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::LauncherError,
+    pack::Pack,
+};
+
+const OPTIONAL_MODS: &str = "optional_mods.json";
+pub const DISABLED_SUFFIX: &str = ".disabled";
+
+// What the UI shows for each optional mod. Modrinth ones are only known by project
+// until they're resolved, so they have no file name
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OptionalModState {
+    pub id: String,
+    pub file_name: Option<String>,
+    pub enabled: bool,
+    pub default_enabled: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct OptionalMods {
+    // By mod key, only what players changed
+    pub enabled: HashMap<String, bool>,
+}
+
+impl OptionalMods {
+    fn path(instance_dir: &Path) -> PathBuf {
+        instance_dir.join(OPTIONAL_MODS)
+    }
+
+    pub fn load(instance_dir: &Path) -> Result<Self, LauncherError> {
+        let path = Self::path(instance_dir);
+        if !path.is_file() {
+            return Ok(Self::default())
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, instance_dir: &Path) -> Result<(), LauncherError> {
+        fs::create_dir_all(instance_dir)?;
+        fs::write(Self::path(instance_dir), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn is_enabled(&self, key: &str, default_enabled: bool) -> bool {
+        self.enabled.get(key).copied().unwrap_or(default_enabled)
+    }
+
+    pub fn set(&mut self, key: String, enabled: bool) {
+        self.enabled.insert(key, enabled);
+    }
+
+    pub fn list(&self, pack: &Pack) -> Result<Vec<OptionalModState>, LauncherError> {
+        let mut states = Vec::new();
+        for modif in pack.mods.iter().filter(|modif| modif.optional) {
+            let id = modif.key()?;
+            states.push(OptionalModState {
+                enabled: self.is_enabled(&id, modif.default_enabled),
+                file_name: Some(modif.local_name()?),
+                default_enabled: modif.default_enabled,
+                id,
+            });
+        }
+        for modif in pack.modrinth.iter().filter(|modif| modif.optional) {
+            states.push(OptionalModState {
+                id: modif.project.clone(),
+                file_name: None,
+                enabled: self.is_enabled(&modif.project, modif.default_enabled),
+                default_enabled: modif.default_enabled,
+            });
+        }
+        Ok(states)
+    }
+}
+
+// Renames the jar to match the choice, returns the name it has in mods/ afterwards
+pub fn apply(mods_dir: &Path, name: &str, enabled: bool) -> Result<String, LauncherError> {
+    let disabled_name = format!("{}{}", name, DISABLED_SUFFIX);
+    let (from, to) = match enabled {
+        true => (disabled_name.as_str(), name),
+        false => (name, disabled_name.as_str()),
+    };
+
+    let from_path = mods_dir.join(from);
+    let to_path = mods_dir.join(to);
+    if from_path.is_file() && !to_path.exists() {
+        fs::rename(&from_path, &to_path)?;
+        println!("Renamed {} to {}", from, to);
+    }
+    Ok(to.to_string())
+}
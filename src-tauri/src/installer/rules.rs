@@ -0,0 +1,111 @@
+// Rule evaluation as done by the vanilla launcher : rules are checked in order,
+// the last one matching the environment decides, and nothing matching means disallowed
+// https://minecraft.wiki/w/Client.json
+
+use std::collections::HashMap;
+
+use regex::Regex;
+
+use super::version_file::{Argument, ArgumentValue, Arguments, Rule, RuleAction};
+
+pub struct Environment {
+    // Named like the version files do : "windows", "osx" or "linux"
+    pub os_name: String,
+    pub os_version: String,
+    pub arch: String,
+    pub features: HashMap<String, bool>,
+}
+
+impl Environment {
+    pub fn current() -> Self {
+        let os_name = match std::env::consts::OS {
+            "macos" => "osx",
+            os => os,
+        };
+
+        let arch = match std::env::consts::ARCH {
+            "aarch64" => "arm64",
+            arch => arch,
+        };
+
+        Self {
+            os_name: os_name.to_string(),
+            os_version: os_info::get().version().to_string(),
+            arch: arch.to_string(),
+            features: HashMap::new(),
+        }
+    }
+}
+
+impl Rule {
+    pub fn matches(&self, env: &Environment) -> bool {
+        if let Some(os) = &self.os {
+            if os.name.as_ref().is_some_and(|name| *name != env.os_name) {
+                return false
+            }
+
+            if os.arch.as_ref().is_some_and(|arch| *arch != env.arch) {
+                return false
+            }
+
+            if let Some(version) = &os.version {
+                match Regex::new(version) {
+                    Ok(regex) if regex.is_match(&env.os_version) => (),
+                    Ok(_) => return false,
+                    Err(err) => {
+                        println!("Invalid OS version rule {} : {}", version, err);
+                        return false
+                    }
+                }
+            }
+        }
+
+        if let Some(features) = &self.features {
+            for (feature, expected) in features {
+                if env.features.get(feature).copied().unwrap_or(false) != *expected {
+                    return false
+                }
+            }
+        }
+
+        true
+    }
+}
+
+pub fn is_allowed(rules: &[Rule], env: &Environment) -> bool {
+    if rules.is_empty() {
+        return true
+    }
+
+    rules.iter()
+        .rev()
+        .find(|rule| rule.matches(env))
+        .is_some_and(|rule| rule.action == RuleAction::Allow)
+}
+
+impl Arguments {
+    pub fn game_args(&self, env: &Environment) -> Vec<String> {
+        allowed_args(&self.game, env)
+    }
+
+    pub fn jvm_args(&self, env: &Environment) -> Vec<String> {
+        allowed_args(&self.jvm, env)
+    }
+}
+
+fn allowed_args(args: &[Argument], env: &Environment) -> Vec<String> {
+    let mut allowed = Vec::new();
+
+    for arg in args {
+        match arg {
+            Argument::Plain(arg) => allowed.push(arg.clone()),
+            Argument::Conditional(arg) if is_allowed(&arg.rules, env) => match &arg.value {
+                ArgumentValue::Single(value) => allowed.push(value.clone()),
+                ArgumentValue::Many(values) => allowed.extend(values.iter().cloned()),
+            },
+            Argument::Conditional(_) => (),
+        }
+    }
+
+    allowed
+}
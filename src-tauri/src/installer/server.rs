@@ -0,0 +1,340 @@
+// Dedicated servers for a pack : the vanilla server jar, the loader libraries and the server
+// side mods in a directory of their own, with eula.txt and a start script. The Java runtime
+// goes in there too, the directory can be copied to the host as is.
+// Only vanilla and Fabric servers for now. Fabric's launcher jar is written here rather than
+// fetched, the one its meta serves downloads the libraries itself on first start
+
+use std::{
+    env,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use tauri::Window;
+
+use crate::{
+    error::LauncherError,
+    maven::MavenCoordinate,
+};
+
+use super::{
+    java::JavaRuntime,
+    lockfile::{self, Lockfile},
+    rules::Environment,
+    version_file::VersionFile,
+    Installer,
+};
+
+const SERVER_JAR: &str = "server.jar";
+const FABRIC_LAUNCHER_JAR: &str = "fabric-server-launch.jar";
+// Read by the launcher jar from the server directory, it writes one pointing at server.jar otherwise
+const FABRIC_LAUNCHER_PROPERTIES: &str = "fabric-server-launcher.properties";
+// Loaders before 0.12 have it in the old package
+const FABRIC_SERVER_LAUNCHER: &str = "net.fabricmc.loader.impl.launch.server.FabricServerLauncher";
+const LEGACY_FABRIC_SERVER_LAUNCHER: &str = "net.fabricmc.loader.launch.server.FabricServerLauncher";
+// What -Xmx gets unless told otherwise, the script keeps it in a variable at the top
+const DEFAULT_SERVER_MEMORY: &str = "4G";
+const EULA_URL: &str = "https://aka.ms/MinecraftEULA";
+
+impl Installer {
+    // Meant for an installer made with InstallSide::Server, instance_dir being the server
+    // directory. Installs the pack's server lockfile when it has one
+    pub async fn install_server(&mut self, window: &Window, accept_eula: bool, memory: Option<&str>) -> Result<(), LauncherError> {
+        let memory = server_memory(memory)?;
+        window.emit("launcher-log", "Installation du serveur...").ok();
+        let server_dir = self.sys.instance_dir.clone();
+
+        let (lockfile, runtime) = match self.game.pack.server_lock.clone() {
+            Some(lock) => {
+                let lockfile = Lockfile::fetch(&self.downloader, &lock.url, &lock.sha1).await?;
+                let platform = lockfile.platform(&Environment::current(), self.game.side)?;
+                let mods = self.queue_locked(&lockfile, platform)?;
+                self.classpath = platform.classpath
+                    .iter()
+                    .map(|path| lockfile::absolute(&server_dir, path))
+                    .collect::<Result<_, _>>()?;
+                let runtime = platform.java.clone();
+                self.downloader.download_queue(window).await?;
+                self.update_managed_mods(mods)?;
+                (lockfile, runtime)
+            },
+            None => self.install_server_files(window).await?,
+        };
+
+        if let Some(runtime) = runtime {
+            runtime.finish(&server_dir)?;
+            self.java = runtime.java(&server_dir);
+        }
+
+        write_eula(&server_dir, accept_eula)?;
+        if !eula_accepted(&server_dir) {
+            window.emit("launcher-log", "Le serveur ne démarrera qu'une fois le CLUF de Minecraft accepté").ok();
+            window.emit("server-eula", EULA_URL).ok();
+        }
+
+        let jar = match self.game.pack.loader {
+            Some(_) => {
+                write_fabric_launcher(&server_dir, &self.classpath, &lockfile.version_file.main_class)?;
+                FABRIC_LAUNCHER_JAR
+            },
+            None => SERVER_JAR,
+        };
+        let script = write_start_script(&server_dir, &self.java, jar, &memory)?;
+        println!("Server start script written to {}", script.display());
+        lockfile.save(&server_dir)?;
+
+        window.emit("launcher-log", "Installation du serveur terminée").ok();
+        Ok(())
+    }
+
+    // Resolves and downloads everything, returns the lockfile of what it got
+    async fn install_server_files(&mut self, window: &Window) -> Result<(Lockfile, Option<JavaRuntime>), LauncherError> {
+        let server_dir = self.sys.instance_dir.clone();
+
+        let version_file = self.fetch_version_file().await?;
+        let server = version_file.downloads.server
+            .as_ref()
+            .ok_or_else(|| LauncherError::ManifestParse(format!(
+                        "Minecraft {} has no dedicated server", version_file.id)))?;
+        self.downloader.push_to_queue(server.url.clone(),
+                                      server_dir.join(SERVER_JAR),
+                                      Some(server.sha1.clone()),
+                                      u64::try_from(server.size).ok())?;
+        println!("Server added to queue successfully");
+
+        let version_file = self.install_server_loader(version_file).await?;
+
+        let runtime = self.install_java(&version_file).await?;
+
+        let resolved = self.resolve_mods().await?;
+        let mods = self.install_mods(resolved.clone())?;
+        println!("Mods added to queue successfully");
+
+        // Taken before downloading, the queue is empty afterwards
+        let mut lockfile = self.lockfile(&version_file, runtime.as_ref(), &resolved)?;
+
+        self.downloader.download_queue(window).await?;
+        lockfile.pin_downloaded(&server_dir)?;
+        self.update_managed_mods(mods)?;
+
+        Ok((lockfile, runtime))
+    }
+
+    // Queues the loader libraries under libraries/ in the server directory, on the classpath
+    // the launcher jar gets. Returns the version file merged with the loader's server profile
+    async fn install_server_loader(&mut self, version_file: VersionFile) -> Result<VersionFile, LauncherError> {
+        let Some(loader) = self.game.pack.loader.clone() else {
+            return Ok(version_file)
+        };
+        let minecraft_version = &self.game.pack.minecraft_version;
+
+        let loader_version = loader.kind.resolve_version(&self.downloader,
+                                                         minecraft_version,
+                                                         loader.version.as_deref())
+            .await?;
+        let profile = loader.kind.server_profile(&self.downloader, minecraft_version, &loader_version)
+            .await?;
+        if profile.inherits_from != version_file.id {
+            return Err(LauncherError::ManifestParse(format!(
+                        "{} inherits from {} instead of {}",
+                        profile.id, profile.inherits_from, version_file.id)));
+        }
+
+        let libraries_dir = self.sys.instance_dir.join("libraries");
+        for lib in &profile.libraries {
+            let repository = lib.url
+                .as_ref()
+                .ok_or_else(|| LauncherError::ManifestParse(format!("{} has no repository", lib.name)))?;
+            let coordinate = MavenCoordinate::parse(&lib.name)?;
+            let path = coordinate.local_path(&libraries_dir);
+            self.downloader.push_to_queue(coordinate.url(repository),
+                                          path.clone(),
+                                          lib.sha1.clone(),
+                                          lib.size.and_then(|size| u64::try_from(size).ok()))?;
+            self.classpath.push(path);
+        }
+        println!("{} server libraries added to queue successfully", loader.kind);
+
+        Ok(version_file.merge(profile))
+    }
+}
+
+// Once the EULA has been shown to whoever runs the server
+pub fn accept_eula(server_dir: &Path) -> Result<(), LauncherError> {
+    write_eula(server_dir, true)
+}
+
+fn eula_accepted(server_dir: &Path) -> bool {
+    fs::read_to_string(server_dir.join("eula.txt"))
+        .map(|eula| eula.lines().any(|line| line.trim() == "eula=true"))
+        .unwrap_or(false)
+}
+
+// Left alone once accepted, so that a later install without the flag doesn't undo it
+fn write_eula(server_dir: &Path, accept: bool) -> Result<(), LauncherError> {
+    if eula_accepted(server_dir) {
+        return Ok(())
+    }
+
+    fs::write(server_dir.join("eula.txt"), format!("# By changing the setting below to TRUE you are indicating your agreement to our EULA ({}).\neula={}\n",
+                             EULA_URL, accept))?;
+    Ok(())
+}
+
+// What the Fabric installer writes for servers : a jar with nothing but the libraries in its
+// manifest and the main class to hand over to, next to the properties naming the vanilla jar
+fn write_fabric_launcher(server_dir: &Path, classpath: &[PathBuf], main_class: &str) -> Result<(), LauncherError> {
+    let launcher = server_launcher_class(classpath)?;
+    let classpath = classpath
+        .iter()
+        .map(|path| lockfile::relative(server_dir, path))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut manifest = String::from("Manifest-Version: 1.0\r\n");
+    manifest.push_str(&manifest_header("Main-Class", launcher));
+    manifest.push_str(&manifest_header("Class-Path", &classpath.join(" ")));
+    manifest.push_str("\r\n");
+
+    let mut zip = zip::ZipWriter::new(fs::File::create(server_dir.join(FABRIC_LAUNCHER_JAR))?);
+    let options = zip::write::FileOptions::default();
+    zip.start_file("META-INF/MANIFEST.MF", options)?;
+    zip.write_all(manifest.as_bytes())?;
+    zip.start_file("fabric-server-launch.properties", options)?;
+    zip.write_all(format!("launch.mainClass={}\n", main_class).as_bytes())?;
+    zip.finish()?;
+
+    fs::write(server_dir.join(FABRIC_LAUNCHER_PROPERTIES), format!("serverJar={}\n", SERVER_JAR))?;
+    Ok(())
+}
+
+// Whichever package the loader jar on the classpath has it in
+fn server_launcher_class(classpath: &[PathBuf]) -> Result<&'static str, LauncherError> {
+    let class = format!("{}.class", FABRIC_SERVER_LAUNCHER.replace('.', "/"));
+    for path in classpath {
+        let mut jar = zip::ZipArchive::new(fs::File::open(path)?)?;
+        if jar.by_name(&class).is_ok() {
+            return Ok(FABRIC_SERVER_LAUNCHER)
+        }
+    }
+    Ok(LEGACY_FABRIC_SERVER_LAUNCHER)
+}
+
+// Manifest lines stop at 72 bytes, longer values go on with lines starting with a space
+fn manifest_header(name: &str, value: &str) -> String {
+    let mut header = String::new();
+    let mut line_len = 0;
+    for c in format!("{}: {}", name, value).chars() {
+        if line_len + c.len_utf8() > 72 {
+            header.push_str("\r\n ");
+            line_len = 1;
+        }
+        header.push(c);
+        line_len += c.len_utf8();
+    }
+    header.push_str("\r\n");
+    header
+}
+
+// Like -Xmx takes it : a number of bytes, or of kilo, mega or gigabytes
+fn server_memory(memory: Option<&str>) -> Result<String, LauncherError> {
+    let memory = memory.unwrap_or(DEFAULT_SERVER_MEMORY).trim();
+    let digits = memory.trim_end_matches(['k', 'K', 'm', 'M', 'g', 'G']);
+    if digits.is_empty() || memory.len() - digits.len() > 1 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(LauncherError::Config(format!("Invalid server memory : {}", memory)));
+    }
+    Ok(memory.to_string())
+}
+
+fn write_start_script(server_dir: &Path, java: &Path, jar: &str, memory: &str) -> Result<PathBuf, LauncherError> {
+    // The script starts in the server directory, the runtime in there is taken relatively
+    let java = java.strip_prefix(server_dir).unwrap_or(java);
+
+    match env::consts::OS {
+        "windows" => {
+            let path = server_dir.join("start.bat");
+            fs::write(&path, format!("@echo off\r\nrem Memory given to the server, raise it for bigger packs\r\nset MEMORY={}\r\ncd /d \"%~dp0\"\r\n\"{}\" -Xmx%MEMORY% -jar {} nogui\r\npause\r\n",
+                                     memory, java.display(), jar))?;
+            Ok(path)
+        },
+        _ => {
+            let path = server_dir.join("start.sh");
+            fs::write(&path, format!("#!/bin/sh\n# Memory given to the server, raise it for bigger packs\nMEMORY={}\ncd \"$(dirname \"$0\")\"\nexec \"{}\" -Xmx$MEMORY -jar {} nogui\n",
+                                     memory, java.display(), jar))?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+            }
+            Ok(path)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use crate::test_dir::TempDir;
+
+    use super::*;
+
+    fn jar(path: &Path, entries: &[&str]) -> PathBuf {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let mut zip = zip::ZipWriter::new(fs::File::create(path).unwrap());
+        for entry in entries {
+            zip.start_file(*entry, zip::write::FileOptions::default()).unwrap();
+        }
+        zip.finish().unwrap();
+        path.to_path_buf()
+    }
+
+    fn read_entry(jar: &Path, name: &str) -> String {
+        let mut jar = zip::ZipArchive::new(fs::File::open(jar).unwrap()).unwrap();
+        let mut content = String::new();
+        jar.by_name(name).unwrap().read_to_string(&mut content).unwrap();
+        content
+    }
+
+    #[test]
+    fn writes_fabric_launcher() {
+        let dir = TempDir::new("fabric-launcher");
+        let classpath = vec![
+            jar(&dir.join("libraries/net/fabricmc/fabric-loader/0.14.21/fabric-loader-0.14.21.jar"),
+                &["net/fabricmc/loader/impl/launch/server/FabricServerLauncher.class"]),
+            jar(&dir.join("libraries/net/fabricmc/intermediary/1.20.1/intermediary-1.20.1.jar"), &[]),
+        ];
+        write_fabric_launcher(&dir, &classpath, "net.fabricmc.loader.impl.launch.knot.KnotServer").unwrap();
+
+        let launcher = dir.join(FABRIC_LAUNCHER_JAR);
+        let manifest = read_entry(&launcher, "META-INF/MANIFEST.MF");
+        assert!(manifest.split("\r\n").all(|line| line.len() <= 72));
+        let manifest = manifest.replace("\r\n ", "");
+        assert!(manifest.contains("Main-Class: net.fabricmc.loader.impl.launch.server.FabricServerLauncher\r\n"));
+        assert!(manifest.contains("Class-Path: libraries/net/fabricmc/fabric-loader/0.14.21/fabric-loader-0.14.21.jar libraries/net/fabricmc/intermediary/1.20.1/intermediary-1.20.1.jar\r\n"));
+        assert_eq!(read_entry(&launcher, "fabric-server-launch.properties"),
+                   "launch.mainClass=net.fabricmc.loader.impl.launch.knot.KnotServer\n");
+        assert_eq!(fs::read_to_string(dir.join(FABRIC_LAUNCHER_PROPERTIES)).unwrap(), "serverJar=server.jar\n");
+    }
+
+    #[test]
+    fn accepting_eula_sticks() {
+        let dir = TempDir::new("server-eula");
+        write_eula(&dir, false).unwrap();
+        assert!(!eula_accepted(&dir));
+
+        accept_eula(&dir).unwrap();
+        write_eula(&dir, false).unwrap();
+        assert!(eula_accepted(&dir));
+    }
+
+    #[test]
+    fn checks_server_memory() {
+        assert_eq!(server_memory(None).unwrap(), "4G");
+        assert_eq!(server_memory(Some(" 6g ")).unwrap(), "6g");
+        assert_eq!(server_memory(Some("2048M")).unwrap(), "2048M");
+        assert!(server_memory(Some("G")).is_err());
+        assert!(server_memory(Some("4GG")).is_err());
+        assert!(server_memory(Some("4G; rm -rf /")).is_err());
+    }
+}
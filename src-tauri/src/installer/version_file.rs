@@ -1,23 +1,32 @@
-// https://transform.tools/json-to-rust-serde
-// Using this to generate the Deserializer's structs for now
+// Started from https://transform.tools/json-to-rust-serde, then loosened so that every
+// version file listed in the version manifest parses, from rd-132211 to the latest snapshot
+
+use std::collections::HashMap;
 
 use serde::Deserialize;
 use serde::Serialize;
-use serde_json::Value;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct VersionFile {
-    pub arguments: Arguments,
+    // Versions before 1.13 only have minecraft_arguments
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arguments: Option<Arguments>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub minecraft_arguments: Option<String>,
     pub asset_index: AssetIndex,
     pub assets: String,
+    #[serde(default)]
     pub compliance_level: i64,
     pub downloads: Downloads,
     pub id: String,
-    pub java_version: JavaVersion,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub java_version: Option<JavaVersion>,
     pub libraries: Vec<Library>,
-    pub logging: Logging,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logging: Option<Logging>,
     pub main_class: String,
+    #[serde(default)]
     pub minimum_launcher_version: i64,
     pub release_time: String,
     pub time: String,
@@ -25,92 +34,121 @@ pub struct VersionFile {
     pub type_field: String,
 }
 
+// Loader profiles (Fabric and the like) only hold what differs from the version they inherit from
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct Arguments {
-    pub game: Vec<Value>,
-    pub jvm: (Jvm, Jvm2, Jvm3, Jvm4, String, String, String, String, String),
+pub struct VersionProfile {
+    pub id: String,
+    pub inherits_from: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub main_class: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arguments: Option<Arguments>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub minecraft_arguments: Option<String>,
+    #[serde(default)]
+    pub libraries: Vec<Library>,
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Jvm {
-    pub rules: Vec<Rule>,
-    pub value: Vec<String>,
-}
+impl VersionFile {
+    // Same merge as the vanilla launcher does for inheritsFrom, the profile's
+    // libraries come first and replace the ones with the same group and artifact
+    pub fn merge(mut self, profile: VersionProfile) -> VersionFile {
+        let key = |lib: &Library| -> String {
+            let mut parts = lib.name.split(':');
+            let group = parts.next().unwrap_or_default();
+            let artifact = parts.next().unwrap_or_default();
+            let classifier = parts.nth(1).unwrap_or_default();
+            format!("{}:{}:{}", group, artifact, classifier)
+        };
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Rule {
-    pub action: String,
-    pub os: Os,
-}
+        let overridden: Vec<String> = profile.libraries.iter().map(key).collect();
+        self.libraries.retain(|lib| !overridden.contains(&key(lib)));
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Os {
-    pub name: String,
-}
+        let mut libraries = profile.libraries;
+        libraries.append(&mut self.libraries);
+        self.libraries = libraries;
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Jvm2 {
-    pub rules: Vec<Rule2>,
-    pub value: String,
-}
+        if let Some(arguments) = profile.arguments {
+            let mut merged = self.arguments.unwrap_or_default();
+            merged.game.extend(arguments.game);
+            merged.jvm.extend(arguments.jvm);
+            self.arguments = Some(merged);
+        }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Rule2 {
-    pub action: String,
-    pub os: Os2,
+        if profile.minecraft_arguments.is_some() {
+            self.minecraft_arguments = profile.minecraft_arguments;
+        }
+
+        if let Some(main_class) = profile.main_class {
+            self.main_class = main_class;
+        }
+
+        self.id = profile.id;
+        self
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct Os2 {
-    pub name: String,
+pub struct Arguments {
+    #[serde(default)]
+    pub game: Vec<Argument>,
+    #[serde(default)]
+    pub jvm: Vec<Argument>,
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Jvm3 {
-    pub rules: Vec<Rule3>,
-    pub value: Vec<String>,
+// Either "--demo" or { "rules": [...], "value": "--demo" | ["--width", "${resolution_width}"] }
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Argument {
+    Plain(String),
+    Conditional(ConditionalArgument),
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct Rule3 {
-    pub action: String,
-    pub os: Os3,
+pub struct ConditionalArgument {
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    pub value: ArgumentValue,
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Os3 {
-    pub name: String,
-    pub version: String,
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ArgumentValue {
+    Single(String),
+    Many(Vec<String>),
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct Jvm4 {
-    pub rules: Vec<Rule4>,
-    pub value: String,
+pub struct Rule {
+    pub action: RuleAction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os: Option<OsRule>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub features: Option<HashMap<String, bool>>,
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Rule4 {
-    pub action: String,
-    pub os: Os4,
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    #[default]
+    Allow,
+    Disallow,
 }
 
+// Every field is optional, "version" is a regex and "arch" only shows up as "x86"
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct Os4 {
-    pub arch: String,
+pub struct OsRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arch: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -127,11 +165,13 @@ pub struct AssetIndex {
 #[serde(rename_all = "camelCase")]
 pub struct Downloads {
     pub client: Client,
-    #[serde(rename = "client_mappings")]
-    pub client_mappings: ClientMappings,
-    pub server: Server,
-    #[serde(rename = "server_mappings")]
-    pub server_mappings: ServerMappings,
+    // Mappings only exist since 1.14.4 and the oldest versions have no server
+    #[serde(rename = "client_mappings", default, skip_serializing_if = "Option::is_none")]
+    pub client_mappings: Option<ClientMappings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<Server>,
+    #[serde(rename = "server_mappings", default, skip_serializing_if = "Option::is_none")]
+    pub server_mappings: Option<ServerMappings>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -176,16 +216,39 @@ pub struct JavaVersion {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Library {
+    #[serde(default)]
     pub downloads: Downloads2,
     pub name: String,
     #[serde(default)]
-    pub rules: Vec<Rule5>,
+    pub rules: Vec<Rule>,
+    // OS name to classifier, "${arch}" standing for 32 or 64 (older versions only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub natives: Option<HashMap<String, String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extract: Option<Extract>,
+    // Loader libraries only give a Maven repository, sometimes with a hash
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha1: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<i64>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Downloads2 {
-    pub artifact: Artifact,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artifact: Option<Artifact>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub classifiers: Option<HashMap<String, Artifact>>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Extract {
+    #[serde(default)]
+    pub exclude: Vec<String>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -197,19 +260,6 @@ pub struct Artifact {
     pub url: String,
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Rule5 {
-    pub action: String,
-    pub os: Os5,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Os5 {
-    pub name: String,
-}
-
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Logging {
@@ -234,3 +284,119 @@ pub struct File {
     pub url: String,
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEGACY: &str = include_str!("../../tests/fixtures/version_1.8.9.json");
+    const MODERN: &str = include_str!("../../tests/fixtures/version_1.20.1.json");
+    const FABRIC_PROFILE: &str = include_str!("../../tests/fixtures/fabric_profile_1.20.1.json");
+
+    fn library<'a>(version_file: &'a VersionFile, name: &str) -> &'a Library {
+        version_file.libraries
+            .iter()
+            .find(|lib| lib.name == name)
+            .unwrap_or_else(|| panic!("{} is missing", name))
+    }
+
+    #[test]
+    fn parses_legacy_version() {
+        let version_file: VersionFile = serde_json::from_str(LEGACY).unwrap();
+
+        assert!(version_file.arguments.is_none());
+        assert!(version_file.minecraft_arguments.as_deref().unwrap().starts_with("--username ${auth_player_name}"));
+        assert!(version_file.downloads.client_mappings.is_none());
+        assert_eq!(version_file.java_version.as_ref().unwrap().component, "jre-legacy");
+
+        let lwjgl = library(&version_file, "org.lwjgl.lwjgl:lwjgl-platform:2.9.4-nightly-20150209");
+        assert!(lwjgl.downloads.artifact.is_none());
+        assert_eq!(lwjgl.natives.as_ref().unwrap()["linux"], "natives-linux");
+        assert!(lwjgl.downloads.classifiers.as_ref().unwrap().contains_key("natives-windows"));
+        assert_eq!(lwjgl.extract.as_ref().unwrap().exclude, vec!["META-INF/"]);
+        assert_eq!(lwjgl.rules.len(), 2);
+        assert_eq!(lwjgl.rules[1].action, RuleAction::Disallow);
+        assert_eq!(lwjgl.rules[1].os.as_ref().unwrap().name.as_deref(), Some("osx"));
+
+        let twitch = library(&version_file, "tv.twitch:twitch-external-platform:4.5");
+        assert_eq!(twitch.natives.as_ref().unwrap()["windows"], "natives-windows-${arch}");
+    }
+
+    #[test]
+    fn parses_modern_version() {
+        let version_file: VersionFile = serde_json::from_str(MODERN).unwrap();
+
+        assert!(version_file.minecraft_arguments.is_none());
+        assert!(version_file.downloads.server_mappings.is_some());
+        let arguments = version_file.arguments.unwrap();
+
+        assert_eq!(arguments.game[0], Argument::Plain("--username".into()));
+        let Argument::Conditional(demo) = &arguments.game[4] else {
+            panic!("--demo should be conditional")
+        };
+        assert_eq!(demo.value, ArgumentValue::Single("--demo".into()));
+        assert!(demo.rules[0].features.as_ref().unwrap()["is_demo_user"]);
+
+        let Argument::Conditional(resolution) = &arguments.game[5] else {
+            panic!("the resolution should be conditional")
+        };
+        let ArgumentValue::Many(values) = &resolution.value else {
+            panic!("the resolution should be a list")
+        };
+        assert_eq!(values, &["--width", "${resolution_width}", "--height", "${resolution_height}"]);
+
+        let Argument::Conditional(windows) = &arguments.jvm[1] else {
+            panic!("the Windows arguments should be conditional")
+        };
+        assert_eq!(windows.rules[0].os.as_ref().unwrap().version.as_deref(), Some("^10\\."));
+        assert_eq!(arguments.jvm.last(), Some(&Argument::Plain("${classpath}".into())));
+    }
+
+    #[test]
+    fn round_trips_arguments() {
+        let version_file: VersionFile = serde_json::from_str(MODERN).unwrap();
+        let json = serde_json::to_string(&version_file).unwrap();
+        assert_eq!(serde_json::from_str::<VersionFile>(&json).unwrap(), version_file);
+    }
+
+    #[test]
+    fn merges_profile() {
+        let version_file: VersionFile = serde_json::from_str(MODERN).unwrap();
+        let profile: VersionProfile = serde_json::from_str(FABRIC_PROFILE).unwrap();
+        let merged = version_file.merge(profile);
+
+        assert_eq!(merged.id, "fabric-loader-0.14.21-1.20.1");
+        assert_eq!(merged.main_class, "net.fabricmc.loader.impl.launch.knot.KnotClient");
+
+        // The profile's lwjgl replaces the vanilla one, the natives have another classifier and stay
+        let names: Vec<&str> = merged.libraries.iter().map(|lib| lib.name.as_str()).collect();
+        assert_eq!(names, vec![
+            "org.ow2.asm:asm:9.5",
+            "org.lwjgl:lwjgl:3.3.2",
+            "net.fabricmc:fabric-loader:0.14.21",
+            "com.mojang:logging:1.1.1",
+            "org.lwjgl:lwjgl:3.3.1:natives-linux",
+        ]);
+
+        let arguments = merged.arguments.unwrap();
+        assert_eq!(arguments.game.len(), 6);
+        assert_eq!(arguments.jvm.last(), Some(&Argument::Plain("-DFabricMcEmu= net.minecraft.client.main.Main ".into())));
+    }
+
+    #[test]
+    fn merge_keeps_legacy_arguments() {
+        let version_file: VersionFile = serde_json::from_str(LEGACY).unwrap();
+        let profile = VersionProfile {
+            id: "1.8.9-forge".into(),
+            inherits_from: "1.8.9".into(),
+            minecraft_arguments: Some("--tweakClass net.minecraftforge.fml.common.launcher.FMLTweaker".into()),
+            ..Default::default()
+        };
+        let merged = version_file.merge(profile);
+
+        assert!(merged.arguments.is_none());
+        assert_eq!(merged.main_class, "net.minecraft.client.main.Main");
+        assert!(merged.minecraft_arguments.unwrap().contains("FMLTweaker"));
+        assert_eq!(merged.libraries.len(), 3);
+    }
+}
@@ -1,9 +1,17 @@
-use std::{fs, io::Read};
+use std::io::ErrorKind;
 
-use walkdir::WalkDir;
+use super::{
+    authenticator::Authenticator,
+    error::LauncherError,
+    installer::{rules::Environment, Installer},
+};
 
-use super::{authenticator::{AuthResult, Authenticator}, installer::Installer};
+use self::arguments::ArgumentBuilder;
 
+mod arguments;
+
+const LAUNCHER_NAME: &str = "PBLM";
+const LAUNCHER_VERSION: &str = "0.1";
 
 pub struct Launcher {
     pub auth: Authenticator,
@@ -12,138 +20,88 @@ pub struct Launcher {
 }
 
 impl Launcher {
-    pub async fn new(auth: Authenticator, installer: Installer) -> Self {
-        let game_dir = String::from(installer.sys.game_dir
-                                    .to_str()
-                                    .expect("Unable to parse game directory"));
-
-        let mut assets_dir = String::from(&game_dir);
-        assets_dir.push_str("assets");
-
-        let mut natives_path = String::from(&game_dir);
-        match std::env::consts::OS {
-            "linux" => natives_path.push_str("natives/linux"),
-            "macos" => natives_path.push_str("natives/osx"),
-            "windows" => natives_path.push_str("natives/windows"),
-            _ => panic!("Unsupported OS"),
+    pub async fn new(auth: Authenticator, installer: Installer) -> Result<Self, LauncherError> {
+        let version_file = installer.version_file
+            .as_ref()
+            .ok_or_else(|| LauncherError::ManifestParse("The game has to be installed before launching".into()))?;
+
+        let assets_dir = installer.sys.game_dir.join("assets");
+        let libraries_dir = installer.sys.game_dir.join("libraries");
+        let natives_dir = installer.natives_dir();
+
+        let separator = match std::env::consts::OS {
+            "windows" => ";",
+            _ => ":",
         };
 
-        let mut client_path = String::from(&game_dir);
-        //if installer.game.mod_loader == true {
-        //    client_path.push_str("/versions");
-        //} else {
-            client_path.push_str("client");
-        //}
-
-        let mut lib_path = String::from(&game_dir);
-        lib_path.push_str("libraries");
-
-        let mut classpath_arg = String::new();
-
-        let win_separator = String::from(';');
-        let lin_separator = String::from(':');
-
-        for file in WalkDir::new(&client_path) {
-            classpath_arg.push_str(file
-                                   .expect("Failed to extract file name")
-                                   .path()
-                                   .to_str()
-                                   .expect("Failed to convert file name to str"));
-            match std::env::consts::OS {
-                "linux" => classpath_arg.push_str(&lin_separator),
-                "macos" => classpath_arg.push_str(&lin_separator),
-                "windows" => classpath_arg.push_str(&win_separator),
-                _ => panic!("Unsupported OS"),
-           }
-        
-        }
-
-        for file in WalkDir::new(&natives_path) {
-            classpath_arg.push_str(file
-                                   .expect("Failed to extract file name")
-                                   .path()
-                                   .to_str()
-                                   .expect("Failed to convert file name to str"));
-            match std::env::consts::OS {
-                "linux" => classpath_arg.push_str(&lin_separator),
-                "macos" => classpath_arg.push_str(&lin_separator),
-                "windows" => classpath_arg.push_str(&win_separator),
-                _ => panic!("Unsupported OS"),
-            }
-
-        }
-
-        for file in WalkDir::new(&lib_path) {
-            classpath_arg.push_str(file
-                                   .expect("Failed to extract file name")
-                                   .path()
-                                   .to_str()
-                                   .expect("Failed to convert file name to str"));
-            match std::env::consts::OS {
-                "linux" => classpath_arg.push_str(&lin_separator),
-                "macos" => classpath_arg.push_str(&lin_separator),
-                "windows" => classpath_arg.push_str(&win_separator),
-                _ => panic!("Unsupported OS"),
-            }
+        let classpath_arg = installer.classpath
+            .iter()
+            .map(|path| path.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(separator);
+
+        let profile = &auth.profile;
+        let mut builder = ArgumentBuilder::new();
+        builder
+            .set("auth_player_name", &profile.username)
+            .set("auth_uuid", &profile.uuid)
+            .set("auth_access_token", &profile.access_token)
+            .set("auth_session", format!("token:{}:{}", profile.access_token, profile.uuid))
+            .set("auth_xuid", "")
+            .set("clientid", "")
+            .set("user_type", "msa")
+            .set("user_properties", "{}")
+            .set("version_name", &version_file.id)
+            .set("version_type", &version_file.type_field)
+            .set("game_directory", installer.sys.instance_dir.to_string_lossy())
+            .set("assets_root", assets_dir.to_string_lossy())
+            .set("game_assets", assets_dir.to_string_lossy())
+            .set("assets_index_name", &version_file.asset_index.id)
+            .set("library_directory", libraries_dir.to_string_lossy())
+            .set("natives_directory", natives_dir.to_string_lossy())
+            .set("classpath", classpath_arg)
+            .set("classpath_separator", separator)
+            .set("launcher_name", LAUNCHER_NAME)
+            .set("launcher_version", LAUNCHER_VERSION);
+
+        let env = Environment::current();
+        let mut launch_args: Vec<String> = builder.jvm_args(version_file, &env);
+
+        if let (Some(logging), Some(path)) = (&version_file.logging, installer.log_config_path(version_file)) {
+            let mut logging_builder = ArgumentBuilder::new();
+            logging_builder.set("path", path.to_string_lossy());
+            launch_args.push(logging_builder.expand(&logging.client.argument));
         }
 
-        //println!("{}", &classpath_arg);
-        let mut profile_path = String::from(&game_dir);
-        profile_path.push_str("profile.json");
-        let mut file = fs::File::open(&profile_path)
-            .expect("Failed to open player profile");
-        let mut file_str = String::new();
-        file.read_to_string(&mut file_str)
-            .expect("Failed to read player profile");
-
-        let profile: AuthResult = serde_json::from_str(&file_str)
-            .expect("Failed to convert profile");
-
-        let mut launch_args: Vec<String> = Vec::new();
         launch_args.extend_from_slice(&[
-                                      format!("-Djava.library.path={}", natives_path),
-                                      format!("-Dminecraft.launcher.brand=PBLM"),
-                                      format!("-Dminecraft.launcher.version=0.1"),
-                                      format!("-cp"),
-                                      classpath_arg, 
-                                      format!("net.fabricmc.loader.impl.launch.knot.KnotClient"),
-                                      format!("-DFabricMcEmu=net.minecraft.client.main.Main"),
-                                      format!("-Xmx6G"),
-                                      format!("-XX:+UnlockExperimentalVMOptions"),
-                                      format!("-XX:+UseG1GC"),
-                                      format!("-XX:G1NewSizePercent=20"),
-                                      format!("-XX:G1ReservePercent=20"),
-                                      format!("-XX:MaxGCPauseMillis=50"),
-                                      format!("-XX:G1HeapRegionSize=32M"),
-                                      format!("--username"),
-                                      profile.username,
-                                      format!("--version"),
-                                      format!("1.19.2"),
-                                      format!("--gameDir"),
-                                      game_dir,
-                                      format!("--assetsDir"),
-                                      assets_dir,
-                                      format!("--assetIndex"),
-                                      format!("1.19"),
-                                      format!("--uuid"),
-                                      profile.uuid,
-                                      format!("--accessToken"),
-                                      profile.access_token,
-                                      format!("--userType"),
-                                      format!("msa"),
-                                      format!("--versionType"),
-                                      format!("release"),
+                                      "-Xmx6G".to_string(),
+                                      "-XX:+UnlockExperimentalVMOptions".to_string(),
+                                      "-XX:+UseG1GC".to_string(),
+                                      "-XX:G1NewSizePercent=20".to_string(),
+                                      "-XX:G1ReservePercent=20".to_string(),
+                                      "-XX:MaxGCPauseMillis=50".to_string(),
+                                      "-XX:G1HeapRegionSize=32M".to_string(),
+                                      version_file.main_class.clone(),
                                       ]);
+        launch_args.extend(builder.game_args(version_file, &env));
 
-        std::process::Command::new("java")
+        let status = match std::process::Command::new(&installer.java)
             .args(&launch_args)
-            .status()
-            .expect("Failed to launch the game");
+            .current_dir(&installer.sys.instance_dir)
+            .status() {
+                Ok(status) => status,
+                Err(err) if err.kind() == ErrorKind::NotFound => return Err(LauncherError::JavaNotFound),
+                Err(err) => return Err(err.into()),
+            };
+
+        if !status.success() {
+            return Err(LauncherError::ProcessExit(status.code()));
+        }
 
-        Self { 
+        Ok(Self { 
             auth, 
             installer, 
             args: launch_args
-        }
+        })
     }
 }
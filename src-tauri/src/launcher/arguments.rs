@@ -0,0 +1,159 @@
+// Expands the ${placeholders} of the version file arguments, for both the
+// "arguments" object and the legacy "minecraftArguments" string of older versions
+
+use std::collections::HashMap;
+
+use crate::installer::{
+    rules::Environment,
+    version_file::VersionFile,
+};
+
+#[derive(Default)]
+pub struct ArgumentBuilder {
+    values: HashMap<String, String>,
+}
+
+impl ArgumentBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, key: &str, value: impl Into<String>) -> &mut Self {
+        self.values.insert(key.to_string(), value.into());
+        self
+    }
+
+    // Unknown placeholders are left as they are
+    pub fn expand(&self, arg: &str) -> String {
+        let mut expanded = String::with_capacity(arg.len());
+        let mut rest = arg;
+
+        while let Some(start) = rest.find("${") {
+            expanded.push_str(&rest[..start]);
+            let placeholder = &rest[start..];
+
+            match placeholder.find('}') {
+                Some(end) => {
+                    match self.values.get(&placeholder[2..end]) {
+                        Some(value) => expanded.push_str(value),
+                        None => expanded.push_str(&placeholder[..=end]),
+                    }
+                    rest = &placeholder[end + 1..];
+                },
+                None => {
+                    expanded.push_str(placeholder);
+                    rest = "";
+                }
+            }
+        }
+
+        expanded.push_str(rest);
+        expanded
+    }
+
+    pub fn jvm_args(&self, version_file: &VersionFile, env: &Environment) -> Vec<String> {
+        let args = match &version_file.arguments {
+            Some(arguments) => arguments.jvm_args(env),
+            // What the vanilla launcher uses when the version file doesn't say
+            None => vec![
+                "-Djava.library.path=${natives_directory}".to_string(),
+                "-cp".to_string(),
+                "${classpath}".to_string(),
+            ],
+        };
+
+        args.iter().map(|arg| self.expand(arg)).collect()
+    }
+
+    pub fn game_args(&self, version_file: &VersionFile, env: &Environment) -> Vec<String> {
+        let args = match (&version_file.arguments, &version_file.minecraft_arguments) {
+            (Some(arguments), _) => arguments.game_args(env),
+            (None, Some(arguments)) => arguments
+                .split_whitespace()
+                .map(String::from)
+                .collect(),
+            (None, None) => Vec::new(),
+        };
+
+        args.iter().map(|arg| self.expand(arg)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(os_name: &str, features: &[(&str, bool)]) -> Environment {
+        Environment {
+            os_name: os_name.to_string(),
+            os_version: "10.0".to_string(),
+            arch: "x86_64".to_string(),
+            features: features.iter().map(|(feature, enabled)| (feature.to_string(), *enabled)).collect(),
+        }
+    }
+
+    fn version_file(fixture: &str) -> VersionFile {
+        serde_json::from_str(fixture).unwrap()
+    }
+
+    #[test]
+    fn expands_placeholders() {
+        let mut builder = ArgumentBuilder::new();
+        builder.set("natives_directory", "/game/natives").set("version_name", "1.20.1");
+
+        assert_eq!(builder.expand("-Djava.library.path=${natives_directory}"), "-Djava.library.path=/game/natives");
+        assert_eq!(builder.expand("${version_name}-${version_name}"), "1.20.1-1.20.1");
+        assert_eq!(builder.expand("${clientid}"), "${clientid}");
+        assert_eq!(builder.expand("a${version_name}${unknown}b"), "a1.20.1${unknown}b");
+        assert_eq!(builder.expand("unclosed ${version_name"), "unclosed ${version_name");
+        assert_eq!(builder.expand("--demo"), "--demo");
+    }
+
+    #[test]
+    fn gates_arguments_on_rules() {
+        let version_file = version_file(include_str!("../../tests/fixtures/version_1.20.1.json"));
+        let mut builder = ArgumentBuilder::new();
+        builder.set("resolution_width", "854").set("resolution_height", "480");
+
+        let game_args = builder.game_args(&version_file, &env("linux", &[]));
+        assert!(!game_args.iter().any(|arg| arg == "--demo" || arg == "--width"));
+
+        let game_args = builder.game_args(&version_file, &env("linux", &[("has_custom_resolution", true)]));
+        assert!(game_args.ends_with(&["--width".to_string(), "854".into(), "--height".into(), "480".into()]));
+        assert!(!game_args.iter().any(|arg| arg == "--demo"));
+
+        let jvm_args = builder.jvm_args(&version_file, &env("osx", &[]));
+        assert_eq!(jvm_args.first().map(String::as_str), Some("-XstartOnFirstThread"));
+        let jvm_args = builder.jvm_args(&version_file, &env("windows", &[]));
+        assert!(jvm_args.iter().any(|arg| arg == "-Dos.name=Windows 10"));
+        assert!(!jvm_args.iter().any(|arg| arg == "-XstartOnFirstThread"));
+    }
+
+    #[test]
+    fn splits_legacy_arguments() {
+        let mut version_file = version_file(include_str!("../../tests/fixtures/version_1.8.9.json"));
+        // As 1.12.2 has it
+        version_file.minecraft_arguments = Some("--username ${auth_player_name} --version ${version_name} --gameDir ${game_directory} --assetsDir ${assets_root} --assetIndex ${assets_index_name} --uuid ${auth_uuid} --accessToken ${auth_access_token} --userType ${user_type} --versionType ${version_type}".into());
+        let mut builder = ArgumentBuilder::new();
+        builder
+            .set("auth_player_name", "Flagadar")
+            .set("version_name", "1.12.2")
+            .set("game_directory", "/game")
+            .set("assets_root", "/game/assets")
+            .set("assets_index_name", "1.12")
+            .set("auth_uuid", "uuid")
+            .set("auth_access_token", "token")
+            .set("user_type", "msa")
+            .set("version_type", "release");
+
+        assert_eq!(builder.game_args(&version_file, &env("linux", &[])), [
+            "--username", "Flagadar", "--version", "1.12.2", "--gameDir", "/game", "--assetsDir", "/game/assets",
+            "--assetIndex", "1.12", "--uuid", "uuid", "--accessToken", "token", "--userType", "msa",
+            "--versionType", "release",
+        ]);
+        // No "arguments" object, the vanilla launcher's defaults
+        assert_eq!(builder.jvm_args(&version_file, &env("linux", &[])), [
+            "-Djava.library.path=${natives_directory}", "-cp", "${classpath}",
+        ]);
+    }
+}
@@ -0,0 +1,128 @@
+// Mod loaders installed over the vanilla version file. Fabric and Quilt both publish
+// a launcher profile on their meta service, Forge and NeoForge bundle theirs in an
+// installer jar. The main class, arguments and libraries all come from that profile
+
+use std::{
+    fmt,
+    fs,
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    downloader::Downloader,
+    error::LauncherError,
+    installer::version_file::VersionProfile,
+};
+
+pub mod forge;
+
+const FABRIC_META: &str = "https://meta.fabricmc.net/v2";
+const QUILT_META: &str = "https://meta.quiltmc.org/v3";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModLoader {
+    Fabric,
+    Quilt,
+    Forge,
+    NeoForge,
+}
+
+#[derive(Debug, Deserialize)]
+struct LoaderEntry {
+    loader: Loader,
+}
+
+#[derive(Debug, Deserialize)]
+struct Loader {
+    version: String,
+    // Quilt doesn't say, its betas have a pre-release suffix instead
+    stable: Option<bool>,
+}
+
+impl Loader {
+    fn is_stable(&self) -> bool {
+        self.stable.unwrap_or_else(|| !self.version.contains('-'))
+    }
+}
+
+impl ModLoader {
+    // Meta service and profile id prefix, for the loaders that have one
+    fn meta(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            ModLoader::Fabric => Some((FABRIC_META, "fabric-loader")),
+            ModLoader::Quilt => Some((QUILT_META, "quilt-loader")),
+            ModLoader::Forge | ModLoader::NeoForge => None,
+        }
+    }
+
+    // The pinned version if there is one, the latest stable loader for this game version otherwise
+    pub async fn resolve_version(&self, downloader: &Downloader, minecraft_version: &str, pinned: Option<&str>) -> Result<String, LauncherError> {
+        if let Some(version) = pinned {
+            return Ok(version.to_string())
+        }
+
+        let Some((meta, _)) = self.meta() else {
+            return forge::latest_version(*self, downloader, minecraft_version).await
+        };
+
+        let loaders: Vec<LoaderEntry> = serde_json::from_value(downloader
+            .to_json(format!("{}/versions/loader/{}", meta, minecraft_version))
+            .await?)?;
+
+        loaders
+            .into_iter()
+            .find(|entry| entry.loader.is_stable())
+            .map(|entry| entry.loader.version)
+            .ok_or_else(|| LauncherError::ManifestParse(format!("No stable {} loader for Minecraft {}", self, minecraft_version)))
+    }
+
+    // Cached in versions/<id>/<id>.json so the game still launches when the meta is unreachable
+    pub async fn get_profile(&self, downloader: &Downloader, game_dir: &Path, minecraft_version: &str, loader_version: &str) -> Result<VersionProfile, LauncherError> {
+        let (meta, prefix) = self.meta()
+            .ok_or_else(|| LauncherError::ManifestParse(format!("{} has no meta profile", self)))?;
+        let id = format!("{}-{}-{}", prefix, loader_version, minecraft_version);
+        let mut cache_path = game_dir.to_path_buf();
+        cache_path.push(format!("versions/{}/{}.json", id, id));
+
+        let url = format!("{}/versions/loader/{}/{}/profile/json", meta, minecraft_version, loader_version);
+        match downloader.to_json(url).await {
+            Ok(profile) => {
+                let profile: VersionProfile = serde_json::from_value(profile)?;
+                if let Some(parent) = cache_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(&cache_path, serde_json::to_string_pretty(&profile)?)?;
+                Ok(profile)
+            },
+            Err(err) if cache_path.is_file() => {
+                println!("Unable to fetch the {} profile, using the cached one : {}", self, err);
+                Ok(serde_json::from_str(&fs::read_to_string(&cache_path)?)?)
+            },
+            Err(err) => Err(err),
+        }
+    }
+
+    // Fabric's server profile, the loader libraries and the main class the server launcher hands over to
+    pub async fn server_profile(&self, downloader: &Downloader, minecraft_version: &str, loader_version: &str) -> Result<VersionProfile, LauncherError> {
+        if *self != ModLoader::Fabric {
+            return Err(LauncherError::LoaderInstall(format!("Server installs only support vanilla and Fabric, not {}", self)))
+        }
+
+        let url = format!("{}/versions/loader/{}/{}/server/json", FABRIC_META, minecraft_version, loader_version);
+        Ok(serde_json::from_value(downloader.to_json(url).await?)?)
+    }
+}
+
+impl fmt::Display for ModLoader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModLoader::Fabric => write!(f, "Fabric"),
+            ModLoader::Quilt => write!(f, "Quilt"),
+            ModLoader::Forge => write!(f, "Forge"),
+            ModLoader::NeoForge => write!(f, "NeoForge"),
+        }
+    }
+}
@@ -0,0 +1,313 @@
+// Forge and NeoForge ship an installer jar rather than a profile : its install_profile.json
+// lists the libraries and the processors that patch the client, and its version.json is
+// the profile merged over the vanilla version file like the other loaders
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    downloader::{self, Downloader},
+    error::LauncherError,
+    installer::version_file::{Library, VersionProfile},
+    maven::MavenCoordinate,
+};
+
+use super::ModLoader;
+
+const FORGE_MAVEN: &str = "https://maven.minecraftforge.net";
+const FORGE_PROMOTIONS: &str = "https://files.minecraftforge.net/net/minecraftforge/forge/promotions_slim.json";
+const NEOFORGE_MAVEN: &str = "https://maven.neoforged.net/releases";
+const NEOFORGE_VERSIONS: &str = "https://maven.neoforged.net/api/maven/versions/releases/net/neoforged/neoforge";
+
+#[derive(Debug, Deserialize)]
+struct Promotions {
+    promos: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MavenVersions {
+    versions: Vec<String>,
+}
+
+// Installers from before 1.13 have a different format and aren't supported
+#[derive(Debug, Deserialize)]
+struct InstallProfile {
+    json: String,
+    #[serde(default)]
+    data: HashMap<String, SidedValue>,
+    #[serde(default)]
+    processors: Vec<Processor>,
+    #[serde(default)]
+    libraries: Vec<Library>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SidedValue {
+    client: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Processor {
+    #[serde(default)]
+    sides: Option<Vec<String>>,
+    jar: String,
+    #[serde(default)]
+    classpath: Vec<String>,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    outputs: HashMap<String, String>,
+}
+
+pub struct ForgeInstall {
+    // None when opened from a jar already on disk
+    pub installer_url: Option<String>,
+    pub installer_jar: PathBuf,
+    install_profile: InstallProfile,
+    pub version_profile: VersionProfile,
+}
+
+// Forge promotes a recommended build per game version, NeoForge versions start with
+// the game version without its leading "1." (1.20.4 gives 20.4.x)
+pub async fn latest_version(kind: ModLoader, downloader: &Downloader, minecraft_version: &str) -> Result<String, LauncherError> {
+    match kind {
+        ModLoader::NeoForge => {
+            let versions: MavenVersions = serde_json::from_value(
+                downloader.to_json(NEOFORGE_VERSIONS.to_string()).await?)?;
+
+            let mut prefix = minecraft_version.trim_start_matches("1.").to_string();
+            if !prefix.contains('.') {
+                prefix.push_str(".0");
+            }
+            prefix.push('.');
+
+            versions.versions
+                .into_iter()
+                .rev()
+                .find(|version| version.starts_with(&prefix) && !version.contains("-beta"))
+                .ok_or_else(|| LauncherError::ManifestParse(format!("No NeoForge release for Minecraft {}", minecraft_version)))
+        },
+        _ => {
+            let promotions: Promotions = serde_json::from_value(
+                downloader.to_json(FORGE_PROMOTIONS.to_string()).await?)?;
+
+            promotions.promos
+                .get(&format!("{}-recommended", minecraft_version))
+                .or_else(|| promotions.promos.get(&format!("{}-latest", minecraft_version)))
+                .cloned()
+                .ok_or_else(|| LauncherError::ManifestParse(format!("No Forge build for Minecraft {}", minecraft_version)))
+        },
+    }
+}
+
+fn installer_coordinate(kind: ModLoader, minecraft_version: &str, loader_version: &str) -> (MavenCoordinate, &'static str) {
+    let (group, artifact, version, repository) = match kind {
+        ModLoader::NeoForge => ("net.neoforged", "neoforge", loader_version.to_string(), NEOFORGE_MAVEN),
+        _ => ("net.minecraftforge", "forge", format!("{}-{}", minecraft_version, loader_version), FORGE_MAVEN),
+    };
+
+    let coordinate = MavenCoordinate {
+        group: group.into(),
+        artifact: artifact.into(),
+        version,
+        classifier: Some("installer".into()),
+        extension: "jar".into(),
+    };
+    (coordinate, repository)
+}
+
+fn read_entry(archive: &mut zip::ZipArchive<fs::File>, name: &str) -> Result<Vec<u8>, LauncherError> {
+    let mut entry = archive.by_name(name.trim_start_matches('/'))?;
+    let mut content = Vec::new();
+    entry.read_to_end(&mut content)?;
+    Ok(content)
+}
+
+fn extract_entry(archive: &mut zip::ZipArchive<fs::File>, name: &str, path: &Path) -> Result<(), LauncherError> {
+    let mut entry = archive.by_name(name.trim_start_matches('/'))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut output = fs::File::create(path)?;
+    io::copy(&mut entry, &mut output)?;
+    Ok(())
+}
+
+fn main_class(jar: &Path) -> Result<String, LauncherError> {
+    let mut archive = zip::ZipArchive::new(fs::File::open(jar)?)?;
+    let manifest = String::from_utf8_lossy(&read_entry(&mut archive, "META-INF/MANIFEST.MF")?).into_owned();
+
+    manifest
+        .lines()
+        .find_map(|line| line.strip_prefix("Main-Class:"))
+        .map(|class| class.trim().to_string())
+        .ok_or_else(|| LauncherError::LoaderInstall(format!("{} has no Main-Class", jar.display())))
+}
+
+impl ForgeInstall {
+    pub async fn fetch(kind: ModLoader, downloader: &Downloader, game_dir: &Path, minecraft_version: &str, loader_version: &str) -> Result<Self, LauncherError> {
+        let (coordinate, repository) = installer_coordinate(kind, minecraft_version, loader_version);
+        let installer_jar = coordinate.local_path(&game_dir.join("libraries"));
+        let installer_url = coordinate.url(repository);
+        if !installer_jar.is_file() {
+            downloader.download_file(installer_url.clone(), &installer_jar).await?;
+        }
+
+        Ok(Self {
+            installer_url: Some(installer_url),
+            ..Self::open(installer_jar)?
+        })
+    }
+
+    pub fn open(installer_jar: PathBuf) -> Result<Self, LauncherError> {
+        let mut archive = zip::ZipArchive::new(fs::File::open(&installer_jar)?)?;
+        let install_profile: InstallProfile = serde_json::from_slice(&read_entry(&mut archive, "install_profile.json")?)
+            .map_err(|err| LauncherError::ManifestParse(format!("{} : {}", installer_jar.display(), err)))?;
+        let version_profile: VersionProfile = serde_json::from_slice(&read_entry(&mut archive, &install_profile.json)?)?;
+
+        Ok(Self {
+            installer_url: None,
+            installer_jar,
+            install_profile,
+            version_profile,
+        })
+    }
+
+    // The processors' own libraries, the version.json ones go through install_libraries.
+    // Libraries without a URL are bundled in the installer under maven/
+    pub fn queue_libraries(&self, downloader: &mut Downloader, libraries_dir: &Path) -> Result<(), LauncherError> {
+        let mut archive = zip::ZipArchive::new(fs::File::open(&self.installer_jar)?)?;
+
+        let libraries = self.install_profile.libraries
+            .iter()
+            .chain(&self.version_profile.libraries);
+        for lib in libraries {
+            let Some(artifact) = &lib.downloads.artifact else {
+                continue
+            };
+            let path = libraries_dir.join(&artifact.path);
+
+            if artifact.url.is_empty() {
+                let bundled = format!("maven/{}", artifact.path);
+                if !path.is_file() && archive.by_name(&bundled).is_ok() {
+                    extract_entry(&mut archive, &bundled, &path)?;
+                }
+            } else if !self.version_profile.libraries.iter().any(|version_lib| version_lib.name == lib.name) {
+                downloader.push_to_queue(artifact.url.clone(),
+                                         path,
+                                         Some(artifact.sha1.clone()),
+                                         u64::try_from(artifact.size).ok())?;
+            }
+        }
+        Ok(())
+    }
+
+    // Needs the libraries and the client jar on disk, skips the processors whose outputs are already there
+    pub fn run_processors(&self, java: &Path, game_dir: &Path, minecraft_jar: &Path) -> Result<(), LauncherError> {
+        let libraries_dir = game_dir.join("libraries");
+        let data_dir = game_dir.join(format!("versions/{}/installer-data", self.version_profile.id));
+        let mut archive = zip::ZipArchive::new(fs::File::open(&self.installer_jar)?)?;
+
+        let mut data: HashMap<String, String> = HashMap::new();
+        for (key, value) in &self.install_profile.data {
+            let value = &value.client;
+            let resolved = if let Some(literal) = value.strip_prefix('\'').and_then(|value| value.strip_suffix('\'')) {
+                literal.to_string()
+            } else if let Some(coordinate) = value.strip_prefix('[').and_then(|value| value.strip_suffix(']')) {
+                MavenCoordinate::parse(coordinate)?.local_path(&libraries_dir).to_string_lossy().into_owned()
+            } else if value.starts_with('/') {
+                let path = data_dir.join(value.trim_start_matches('/'));
+                extract_entry(&mut archive, value, &path)?;
+                path.to_string_lossy().into_owned()
+            } else {
+                value.clone()
+            };
+            data.insert(key.clone(), resolved);
+        }
+        data.insert("SIDE".into(), "client".into());
+        data.insert("MINECRAFT_JAR".into(), minecraft_jar.to_string_lossy().into_owned());
+        data.insert("MINECRAFT_VERSION".into(), self.version_profile.inherits_from.clone());
+        data.insert("ROOT".into(), game_dir.to_string_lossy().into_owned());
+        data.insert("INSTALLER".into(), self.installer_jar.to_string_lossy().into_owned());
+        data.insert("LIBRARY_DIR".into(), libraries_dir.to_string_lossy().into_owned());
+
+        let resolve = |arg: &str| -> Result<String, LauncherError> {
+            if let Some(literal) = arg.strip_prefix('\'').and_then(|arg| arg.strip_suffix('\'')) {
+                return Ok(literal.to_string())
+            }
+            if let Some(key) = arg.strip_prefix('{').and_then(|arg| arg.strip_suffix('}')) {
+                return data.get(key)
+                    .cloned()
+                    .ok_or_else(|| LauncherError::LoaderInstall(format!("Unknown processor data {}", key)))
+            }
+            if let Some(coordinate) = arg.strip_prefix('[').and_then(|arg| arg.strip_suffix(']')) {
+                return Ok(MavenCoordinate::parse(coordinate)?.local_path(&libraries_dir).to_string_lossy().into_owned())
+            }
+            Ok(arg.to_string())
+        };
+
+        let separator = match std::env::consts::OS {
+            "windows" => ";",
+            _ => ":",
+        };
+
+        for processor in &self.install_profile.processors {
+            if processor.sides.as_ref().is_some_and(|sides| !sides.iter().any(|side| side == "client")) {
+                continue
+            }
+
+            let mut outputs = Vec::new();
+            for (path, sha1) in &processor.outputs {
+                outputs.push((PathBuf::from(resolve(path)?), resolve(sha1)?));
+            }
+            let done = !outputs.is_empty() && outputs
+                .iter()
+                .all(|(path, sha1)| downloader::file_sha1(path).is_ok_and(|hash| hash.eq_ignore_ascii_case(sha1)));
+            if done {
+                continue
+            }
+
+            let jar = MavenCoordinate::parse(&processor.jar)?.local_path(&libraries_dir);
+            let mut classpath = vec![jar.to_string_lossy().into_owned()];
+            for lib in &processor.classpath {
+                classpath.push(MavenCoordinate::parse(lib)?.local_path(&libraries_dir).to_string_lossy().into_owned());
+            }
+
+            let mut args = Vec::new();
+            for arg in &processor.args {
+                args.push(resolve(arg)?);
+            }
+
+            println!("Running processor {}", processor.jar);
+            let status = match Command::new(java)
+                .arg("-cp")
+                .arg(classpath.join(separator))
+                .arg(main_class(&jar)?)
+                .args(&args)
+                .current_dir(game_dir)
+                .status() {
+                    Ok(status) => status,
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => return Err(LauncherError::JavaNotFound),
+                    Err(err) => return Err(err.into()),
+                };
+
+            if !status.success() {
+                return Err(LauncherError::LoaderInstall(format!("{} exited with {}", processor.jar, status)));
+            }
+
+            for (path, sha1) in &outputs {
+                if !downloader::file_sha1(path)?.eq_ignore_ascii_case(sha1) {
+                    return Err(LauncherError::Checksum(path.clone()));
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,37 +1,146 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+use std::path::{Path, PathBuf};
+
 use launcher::Launcher;
 use tauri::Window;
 
 use authenticator::Authenticator;
-use installer::Installer;
+use downloader::Downloader;
+use error::LauncherError;
+use installer::{
+    optional_mods::{self, OptionalModState, OptionalMods},
+    Installer,
+};
+use modpack::{
+    curseforge::{self, CurseForgeApi},
+    mrpack,
+};
+use pack::{InstallSide, Pack};
 
 pub mod launcher;
 pub mod installer;
 pub mod downloader;
 pub mod authenticator;
-pub mod mods;
-pub mod fabric;
+pub mod loader;
+pub mod error;
+pub mod maven;
+pub mod pack;
+pub mod modpack;
+pub mod modrinth;
+pub mod version_range;
+#[cfg(test)]
+mod test_dir;
+
+// The main pack when no instance is given
+async fn load_pack(game_dir: &Path, instance: Option<String>) -> Result<(Pack, PathBuf), LauncherError> {
+    match instance {
+        Some(instance) => {
+            let instance_dir = pack::instance_dir(game_dir, &instance);
+            Ok((Pack::load(&instance_dir)?, instance_dir))
+        },
+        None => {
+            let pack = Pack::fetch(&Downloader::new(), game_dir, &pack::manifest_url())
+                .await?;
+            Ok((pack, game_dir.to_path_buf()))
+        },
+    }
+}
 
 #[tauri::command]
-async fn launch(window: Window) {
-    let mut installer = Installer::new("1.19.2");
+async fn launch(window: Window, instance: Option<String>) -> Result<(), LauncherError> {
+    let game_dir = installer::game_dir()?;
+    let (pack, instance_dir) = load_pack(&game_dir, instance).await?;
+    let mut installer = Installer::new(pack, instance_dir, InstallSide::Client)?;
 
-    let auth = Authenticator::new(&installer.sys.game_dir, &window)
-        .await;
+    let auth = Authenticator::new(&game_dir, &window)
+        .await?;
 
     installer.install(&window)
+        .await?;
+
+    window.emit("launcher-log", "En cours de lancement").ok();
+    Launcher::new(auth, installer)
+        .await?;
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn import_mrpack(window: Window, path: String) -> Result<String, LauncherError> {
+    let game_dir = installer::game_dir()?;
+    window.emit("launcher-log", "Import du modpack...").ok();
+    let instance = mrpack::import(Path::new(&path), &game_dir, &window)
+        .await?;
+    window.emit("launcher-log", "Modpack importé").ok();
+    Ok(instance)
+}
+
+#[tauri::command]
+async fn import_curseforge(window: Window, path: String) -> Result<String, LauncherError> {
+    let game_dir = installer::game_dir()?;
+    window.emit("launcher-log", "Import du modpack...").ok();
+    let instance = curseforge::import(Path::new(&path), &game_dir, &CurseForgeApi::new()?, &window)
+        .await?;
+    window.emit("launcher-log", "Modpack importé").ok();
+    Ok(instance)
+}
+
+#[tauri::command]
+async fn export_mrpack(path: String, instance: Option<String>) -> Result<(), LauncherError> {
+    let game_dir = installer::game_dir()?;
+    let (pack, instance_dir) = load_pack(&game_dir, instance).await?;
+    mrpack::export(&pack, &instance_dir, &Downloader::new(), Path::new(&path))
         .await
-        .expect("Unable to install the game");
+}
+
+// The pack's dedicated server in `path`, its server side mods only. `memory` is what -Xmx gets, like "6G"
+#[tauri::command]
+async fn install_server(window: Window, path: String, instance: Option<String>, accept_eula: bool, memory: Option<String>) -> Result<(), LauncherError> {
+    let game_dir = installer::game_dir()?;
+    let (pack, _) = load_pack(&game_dir, instance).await?;
+    Installer::new(pack, PathBuf::from(path), InstallSide::Server)?
+        .install_server(&window, accept_eula, memory.as_deref())
+        .await
+}
+
+// Once the player agreed to https://aka.ms/MinecraftEULA, the server refuses to start before that
+#[tauri::command]
+fn accept_server_eula(path: String) -> Result<(), LauncherError> {
+    installer::server::accept_eula(Path::new(&path))
+}
+
+#[tauri::command]
+async fn list_optional_mods(instance: Option<String>) -> Result<Vec<OptionalModState>, LauncherError> {
+    let game_dir = installer::game_dir()?;
+    let (pack, instance_dir) = load_pack(&game_dir, instance).await?;
+    OptionalMods::load(&instance_dir)?.list(&pack)
+}
+
+// Jars already in mods/ are renamed right away, the others follow on the next install
+#[tauri::command]
+async fn set_optional_mod(instance: Option<String>, id: String, enabled: bool) -> Result<(), LauncherError> {
+    let game_dir = installer::game_dir()?;
+    let (pack, instance_dir) = load_pack(&game_dir, instance).await?;
+
+    let mut optional = OptionalMods::load(&instance_dir)?;
+    let state = optional.list(&pack)?
+        .into_iter()
+        .find(|state| state.id == id)
+        .ok_or_else(|| LauncherError::ManifestParse(format!("{} is not an optional mod of {}", id, pack.name)))?;
+
+    optional.set(id, enabled);
+    optional.save(&instance_dir)?;
 
-    window.emit("launcher-log", "En cours de lancement").unwrap();
-    let launcher = Launcher::new(auth, installer)
-        .await;
+    if let Some(file_name) = state.file_name {
+        optional_mods::apply(&instance_dir.join("mods"), &file_name, enabled)?;
+    }
+    Ok(())
 }
 
 fn main() {
   tauri::Builder::default()
-    .invoke_handler(tauri::generate_handler![launch])
+    .invoke_handler(tauri::generate_handler![launch, import_mrpack, import_curseforge, export_mrpack, install_server, accept_server_eula, list_optional_mods, set_optional_mod])
     .run(tauri::generate_context!())
     .expect("Error while running tauri application");
 }
@@ -0,0 +1,82 @@
+// Maven coordinates as used by version files and loaders : "group:artifact:version[:classifier][@extension]"
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::error::LauncherError;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MavenCoordinate {
+    pub group: String,
+    pub artifact: String,
+    pub version: String,
+    pub classifier: Option<String>,
+    pub extension: String,
+}
+
+impl MavenCoordinate {
+    pub fn parse(coordinate: &str) -> Result<Self, LauncherError> {
+        let (coordinate, extension) = match coordinate.split_once('@') {
+            Some((coordinate, extension)) => (coordinate, extension),
+            None => (coordinate, "jar"),
+        };
+
+        let parts: Vec<&str> = coordinate.split(':').collect();
+        match parts.as_slice() {
+            [group, artifact, version] => Ok(Self {
+                group: group.to_string(),
+                artifact: artifact.to_string(),
+                version: version.to_string(),
+                classifier: None,
+                extension: extension.to_string(),
+            }),
+            [group, artifact, version, classifier] => Ok(Self {
+                group: group.to_string(),
+                artifact: artifact.to_string(),
+                version: version.to_string(),
+                classifier: Some(classifier.to_string()),
+                extension: extension.to_string(),
+            }),
+            _ => Err(LauncherError::ManifestParse(format!("Invalid Maven coordinate {}", coordinate))),
+        }
+    }
+
+    pub fn file_name(&self) -> String {
+        match &self.classifier {
+            Some(classifier) => format!("{}-{}-{}.{}", self.artifact, self.version, classifier, self.extension),
+            None => format!("{}-{}.{}", self.artifact, self.version, self.extension),
+        }
+    }
+
+    // Relative path in a Maven repository, the same one Artifact::path holds
+    pub fn path(&self) -> String {
+        format!("{}/{}/{}/{}",
+                self.group.replace('.', "/"),
+                self.artifact,
+                self.version,
+                self.file_name())
+    }
+
+    pub fn local_path(&self, libraries_dir: &Path) -> PathBuf {
+        libraries_dir.join(self.path())
+    }
+
+    pub fn url(&self, repository: &str) -> String {
+        format!("{}/{}", repository.trim_end_matches('/'), self.path())
+    }
+}
+
+impl fmt::Display for MavenCoordinate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.group, self.artifact, self.version)?;
+        if let Some(classifier) = &self.classifier {
+            write!(f, ":{}", classifier)?;
+        }
+        if self.extension != "jar" {
+            write!(f, "@{}", self.extension)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+// Modpacks made for other launchers, imported as instances
+
+use std::{
+    fs,
+    io::{self, Read, Seek, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use crate::error::LauncherError;
+
+pub mod mrpack;
+pub mod curseforge;
+
+// What of an instance is exported next to the mods. Saves, logs and everything the launcher
+// manages stay out, the main pack's instance being the game directory itself
+const EXPORTED_OVERRIDES: [&str; 6] = ["config", "defaultconfigs", "kubejs", "resourcepacks", "shaderpacks", "options.txt"];
+
+// Paths come from the archive, anything escaping the instance is refused
+pub(crate) fn relative_path(path: &str) -> Result<PathBuf, LauncherError> {
+    let path = PathBuf::from(path);
+    if path.components().all(|component| matches!(component, Component::Normal(_))) {
+        Ok(path)
+    } else {
+        Err(LauncherError::ManifestParse(format!("Refusing to write outside the instance : {}", path.display())))
+    }
+}
+
+// Copies the content of the given archive directories over the instance
+fn extract_overrides<R: Read + Seek>(archive: &mut zip::ZipArchive<R>, overrides: &[&str], instance_dir: &Path) -> Result<(), LauncherError> {
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let name = entry.name().to_string();
+        let Some(relative) = overrides.iter().find_map(|dir| name.strip_prefix(&format!("{}/", dir.trim_end_matches('/')))) else {
+            continue
+        };
+        if relative.is_empty() || entry.is_dir() {
+            continue
+        }
+
+        let path = instance_dir.join(relative_path(relative)?);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        io::copy(&mut entry, &mut fs::File::create(path)?)?;
+    }
+    Ok(())
+}
+
+// The other way around, the exported parts of the instance under `dir` in the archive
+fn add_overrides<W: Write + Seek>(zip: &mut zip::ZipWriter<W>, instance_dir: &Path, dir: &str) -> Result<(), LauncherError> {
+    let mut pending: Vec<PathBuf> = EXPORTED_OVERRIDES
+        .iter()
+        .map(|name| instance_dir.join(name))
+        .collect();
+
+    while let Some(path) = pending.pop() {
+        if path.is_dir() {
+            pending.extend(fs::read_dir(&path)?.filter_map(|entry| entry.ok()).map(|entry| entry.path()));
+        } else if path.is_file() {
+            let relative = path.strip_prefix(instance_dir).unwrap_or(&path);
+            let name = format!("{}/{}", dir, relative.to_string_lossy().replace('\\', "/"));
+            zip.start_file(name, zip::write::FileOptions::default())?;
+            io::copy(&mut fs::File::open(&path)?, zip)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::test_dir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn overrides_round_trip() {
+        let instance_dir = TempDir::new("export");
+        fs::create_dir_all(instance_dir.join("config/sodium")).unwrap();
+        fs::write(instance_dir.join("config/sodium/options.json"), "{}").unwrap();
+        fs::write(instance_dir.join("options.txt"), "fov:0.5").unwrap();
+        fs::create_dir_all(instance_dir.join("saves/world")).unwrap();
+        fs::write(instance_dir.join("saves/world/level.dat"), "").unwrap();
+        fs::write(instance_dir.join("managed_mods.json"), "{}").unwrap();
+
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        add_overrides(&mut zip, &instance_dir, "overrides").unwrap();
+        let mut archive = zip::ZipArchive::new(zip.finish().unwrap()).unwrap();
+
+        let mut names: Vec<&str> = archive.file_names().collect();
+        names.sort();
+        assert_eq!(names, vec!["overrides/config/sodium/options.json", "overrides/options.txt"]);
+
+        let imported_dir = TempDir::new("import");
+        extract_overrides(&mut archive, &["overrides"], &imported_dir).unwrap();
+        assert_eq!(fs::read_to_string(imported_dir.join("config/sodium/options.json")).unwrap(), "{}");
+        assert_eq!(fs::read_to_string(imported_dir.join("options.txt")).unwrap(), "fov:0.5");
+        assert!(!imported_dir.join("saves").exists());
+    }
+
+    #[test]
+    fn refuses_escaping_paths() {
+        assert!(relative_path("config/a.json").is_ok());
+        assert!(relative_path("../a.json").is_err());
+        assert!(relative_path("/etc/passwd").is_err());
+    }
+}
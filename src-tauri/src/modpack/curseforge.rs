@@ -0,0 +1,408 @@
+// CurseForge exports : a zip with a manifest.json listing project and file ids, and
+// the overrides to copy over the instance. The ids go through a resolver to get the jars
+
+use std::{
+    env,
+    fs,
+    future::Future,
+    io::Read,
+    path::Path,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    downloader::{DownloadProgress, Downloader, ProgressSink},
+    error::LauncherError,
+    loader::ModLoader,
+    pack::{self, Pack, PackLoader, PackMod, Side},
+};
+
+use super::{extract_overrides, relative_path};
+
+const MANIFEST: &str = "manifest.json";
+const CURSEFORGE_API: &str = "https://api.curseforge.com/v1";
+const CURSEFORGE_API_KEY_ENV: &str = "CURSEFORGE_API_KEY";
+const SHA1_ALGO: u32 = 1;
+// Project classes, what decides where a file goes
+const MODS_CLASS: u32 = 6;
+const RESOURCE_PACKS_CLASS: u32 = 12;
+const SHADERS_CLASS: u32 = 6552;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    minecraft: ManifestMinecraft,
+    name: String,
+    #[serde(default)]
+    version: String,
+    files: Vec<ManifestFile>,
+    #[serde(default = "default_overrides")]
+    overrides: String,
+}
+
+fn default_overrides() -> String {
+    "overrides".into()
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ManifestMinecraft {
+    version: String,
+    #[serde(default)]
+    mod_loaders: Vec<ManifestLoader>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ManifestLoader {
+    id: String,
+    #[serde(default)]
+    primary: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ManifestFile {
+    #[serde(rename = "projectID")]
+    project_id: u64,
+    #[serde(rename = "fileID")]
+    file_id: u64,
+    #[serde(default = "default_required")]
+    required: bool,
+}
+
+fn default_required() -> bool {
+    true
+}
+
+// What a resolver gives back for a project and file id
+#[derive(Debug, Clone)]
+pub struct CurseForgeFile {
+    // The class of the project, mods, resource packs, shaders...
+    pub class_id: u32,
+    pub file_name: String,
+    pub url: String,
+    pub sha1: String,
+    pub size: u64,
+}
+
+pub trait CurseForgeResolver {
+    fn resolve(&self, project_id: u64, file_id: u64) -> impl Future<Output = Result<CurseForgeFile, LauncherError>> + Send;
+}
+
+// The official API, it needs a key from https://console.curseforge.com
+pub struct CurseForgeApi {
+    client: reqwest::Client,
+    api_key: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiResponse<T> {
+    data: T,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiProject {
+    class_id: u32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiFile {
+    file_name: String,
+    file_length: u64,
+    // Null when the author opted out of third party downloads
+    download_url: Option<String>,
+    #[serde(default)]
+    hashes: Vec<ApiHash>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiHash {
+    value: String,
+    algo: u32,
+}
+
+impl CurseForgeApi {
+    pub fn new() -> Result<Self, LauncherError> {
+        let api_key = env::var(CURSEFORGE_API_KEY_ENV)
+            .map_err(|_| LauncherError::Config(format!("{} is needed to import CurseForge packs", CURSEFORGE_API_KEY_ENV)))?;
+
+        Ok(Self {
+            client: reqwest::Client::new(),
+            api_key,
+        })
+    }
+}
+
+impl CurseForgeApi {
+    async fn get<T: serde::de::DeserializeOwned>(&self, url: String) -> Result<T, LauncherError> {
+        let response: ApiResponse<T> = self.client
+            .get(url)
+            .header("x-api-key", &self.api_key)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(response.data)
+    }
+}
+
+impl CurseForgeResolver for CurseForgeApi {
+    async fn resolve(&self, project_id: u64, file_id: u64) -> Result<CurseForgeFile, LauncherError> {
+        let project: ApiProject = self.get(format!("{}/mods/{}", CURSEFORGE_API, project_id)).await?;
+        let file: ApiFile = self.get(format!("{}/mods/{}/files/{}", CURSEFORGE_API, project_id, file_id)).await?;
+        let url = file.download_url
+            .ok_or_else(|| LauncherError::Network(format!("{} can't be downloaded outside of CurseForge", file.file_name)))?;
+
+        Ok(CurseForgeFile {
+            sha1: file.hashes
+                .into_iter()
+                .find(|hash| hash.algo == SHA1_ALGO)
+                .map(|hash| hash.value)
+                .ok_or_else(|| LauncherError::ManifestParse(format!("{} has no sha1", file.file_name)))?,
+            size: file.file_length,
+            class_id: project.class_id,
+            file_name: file.file_name,
+            url,
+        })
+    }
+}
+
+// "forge-43.2.0", "fabric-0.14.21"... the primary one wins when there are several
+fn pack_loader(loaders: &[ManifestLoader]) -> Result<Option<PackLoader>, LauncherError> {
+    let Some(loader) = loaders.iter().find(|loader| loader.primary).or(loaders.first()) else {
+        return Ok(None)
+    };
+
+    let (kind, version) = loader.id
+        .split_once('-')
+        .ok_or_else(|| LauncherError::ManifestParse(format!("Unknown mod loader {}", loader.id)))?;
+    let kind = match kind {
+        "fabric" => ModLoader::Fabric,
+        "quilt" => ModLoader::Quilt,
+        "forge" => ModLoader::Forge,
+        "neoforge" => ModLoader::NeoForge,
+        _ => return Err(LauncherError::ManifestParse(format!("Unknown mod loader {}", loader.id))),
+    };
+
+    Ok(Some(PackLoader {
+        kind,
+        version: Some(version.to_string()),
+    }))
+}
+
+// Creates instances/<name> from the pack, returns the instance name. Optional files stay optional.
+// Resource packs and shaders are a one time download like the overrides, only mods are part of the pack
+pub async fn import(zip: &Path, game_dir: &Path, resolver: &impl CurseForgeResolver, sink: &dyn ProgressSink) -> Result<String, LauncherError> {
+    let mut archive = zip::ZipArchive::new(fs::File::open(zip)?)?;
+
+    let manifest: Manifest = {
+        let mut entry = archive.by_name(MANIFEST)?;
+        let mut content = String::new();
+        entry.read_to_string(&mut content)?;
+        serde_json::from_str(&content)?
+    };
+
+    let instance = pack::instance_name(&manifest.name);
+    let instance_dir = pack::instance_dir(game_dir, &instance);
+    let mut downloader = Downloader::from_env();
+    let mut mods = Vec::new();
+    for (i, file) in manifest.files.iter().enumerate() {
+        let resolved = resolver.resolve(file.project_id, file.file_id).await?;
+        sink.progress(&DownloadProgress {
+            total_files: manifest.files.len(),
+            completed_files: i + 1,
+            current_file: Some(resolved.file_name.clone()),
+            ..Default::default()
+        });
+
+        let dir = match resolved.class_id {
+            MODS_CLASS => {
+                mods.push(PackMod {
+                    id: Some(format!("curseforge:{}", file.project_id)),
+                    url: resolved.url,
+                    file_name: Some(resolved.file_name),
+                    sha1: resolved.sha1,
+                    size: resolved.size,
+                    side: Side::Both,
+                    optional: !file.required,
+                    default_enabled: true,
+                });
+                continue
+            },
+            RESOURCE_PACKS_CLASS => "resourcepacks",
+            SHADERS_CLASS => "shaderpacks",
+            class_id => {
+                println!("Skipping {}, CurseForge class {} isn't supported", resolved.file_name, class_id);
+                continue
+            },
+        };
+        downloader.push_to_queue(resolved.url,
+                                 instance_dir.join(dir).join(relative_path(&resolved.file_name)?),
+                                 Some(resolved.sha1),
+                                 Some(resolved.size))?;
+    }
+
+    extract_overrides(&mut archive, &[manifest.overrides.as_str()], &instance_dir)?;
+    downloader.download_queue(sink).await?;
+
+    let pack = Pack {
+        name: manifest.name,
+        version: manifest.version,
+        minecraft_version: manifest.minecraft.version,
+        loader: pack_loader(&manifest.minecraft.mod_loaders)?,
+        mods,
+        modrinth: Vec::new(),
+        lock: None,
+        server_lock: None,
+    };
+    pack.save(&instance_dir)?;
+    println!("Imported {} as instance {}", pack.name, instance);
+
+    Ok(instance)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        io::Write,
+        path::PathBuf,
+    };
+
+    use crate::test_dir::TempDir;
+
+    use super::*;
+
+    struct LocalResolver {
+        files: HashMap<(u64, u64), CurseForgeFile>,
+    }
+
+    impl CurseForgeResolver for LocalResolver {
+        async fn resolve(&self, project_id: u64, file_id: u64) -> Result<CurseForgeFile, LauncherError> {
+            self.files
+                .get(&(project_id, file_id))
+                .cloned()
+                .ok_or_else(|| LauncherError::Network(format!("No file {} for project {}", file_id, project_id)))
+        }
+    }
+
+    struct NoProgress;
+
+    impl ProgressSink for NoProgress {
+        fn progress(&self, _progress: &DownloadProgress) {}
+    }
+
+    const FIXTURE_MANIFEST: &str = r#"{
+        "minecraft": {
+            "version": "1.19.2",
+            "modLoaders": [{ "id": "fabric-0.14.21", "primary": true }]
+        },
+        "manifestType": "minecraftModpack",
+        "manifestVersion": 1,
+        "name": "Test Pack",
+        "version": "1.0.0",
+        "files": [
+            { "projectID": 306612, "fileID": 4413246, "required": true },
+            { "projectID": 394468, "fileID": 4425565, "required": false },
+            { "projectID": 236822, "fileID": 4419112 },
+            { "projectID": 382458, "fileID": 4412970 },
+            { "projectID": 610211, "fileID": 4407711 }
+        ],
+        "overrides": "overrides"
+    }"#;
+
+    fn fixture_zip(dir: &Path) -> PathBuf {
+        let path = dir.join("pack.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&path).unwrap());
+        let options = zip::write::FileOptions::default();
+        zip.start_file(MANIFEST, options).unwrap();
+        zip.write_all(FIXTURE_MANIFEST.as_bytes()).unwrap();
+        zip.start_file("overrides/config/voicechat/voicechat-client.properties", options).unwrap();
+        zip.write_all(b"voice_chat_volume=1.0").unwrap();
+        // Already there with the right hash, so the import downloads nothing
+        zip.start_file("overrides/resourcepacks/Faithful.zip", options).unwrap();
+        zip.start_file("overrides/shaderpacks/Complementary.zip", options).unwrap();
+        zip.start_file("overrides/options.txt", options).unwrap();
+        zip.write_all(b"fov:0.5").unwrap();
+        zip.start_file("modlist.html", options).unwrap();
+        zip.write_all(b"<ul></ul>").unwrap();
+        zip.finish().unwrap();
+        path
+    }
+
+    fn file(name: &str) -> CurseForgeFile {
+        classified(MODS_CLASS, name)
+    }
+
+    fn classified(class_id: u32, name: &str) -> CurseForgeFile {
+        CurseForgeFile {
+            class_id,
+            file_name: name.to_string(),
+            url: format!("https://edge.forgecdn.net/files/{}", name),
+            sha1: "da39a3ee5e6b4b0d3255bfef95601890afd80709".into(),
+            size: 0,
+        }
+    }
+
+    #[test]
+    fn imports_manifest_and_overrides() {
+        let game_dir = TempDir::new("curseforge");
+
+        let resolver = LocalResolver {
+            files: HashMap::from([
+                ((306612, 4413246), file("fabric-api-0.76.0+1.19.2.jar")),
+                ((394468, 4425565), file("sodium-fabric-mc1.19.2-0.4.4.jar")),
+                ((236822, 4419112), classified(RESOURCE_PACKS_CLASS, "Faithful.zip")),
+                ((382458, 4412970), classified(SHADERS_CLASS, "Complementary.zip")),
+                // A world, nowhere to put it
+                ((610211, 4407711), classified(17, "Skyblock.zip")),
+            ]),
+        };
+
+        let instance = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(import(&fixture_zip(&game_dir), &game_dir, &resolver, &NoProgress))
+            .unwrap();
+        assert_eq!(instance, "test-pack");
+
+        let instance_dir = pack::instance_dir(&game_dir, &instance);
+        let pack = Pack::load(&instance_dir).unwrap();
+        assert_eq!(pack.minecraft_version, "1.19.2");
+        let loader = pack.loader.unwrap();
+        assert_eq!(loader.kind, ModLoader::Fabric);
+        assert_eq!(loader.version.as_deref(), Some("0.14.21"));
+
+        // Resource packs and shaders aren't mods of the pack
+        assert_eq!(pack.mods.len(), 2);
+        assert!(instance_dir.join("resourcepacks/Faithful.zip").is_file());
+        assert!(instance_dir.join("shaderpacks/Complementary.zip").is_file());
+        assert_eq!(pack.mods[0].file_name.as_deref(), Some("fabric-api-0.76.0+1.19.2.jar"));
+        assert_eq!(pack.mods[0].id.as_deref(), Some("curseforge:306612"));
+        assert!(!pack.mods[0].optional);
+        assert!(pack.mods[1].optional);
+
+        assert_eq!(fs::read_to_string(instance_dir.join("config/voicechat/voicechat-client.properties")).unwrap(), "voice_chat_volume=1.0");
+        assert_eq!(fs::read_to_string(instance_dir.join("options.txt")).unwrap(), "fov:0.5");
+        assert!(!instance_dir.join("modlist.html").exists());
+    }
+
+    #[test]
+    fn unresolved_files_fail_the_import() {
+        let game_dir = TempDir::new("curseforge");
+
+        let resolver = LocalResolver { files: HashMap::new() };
+        let result = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(import(&fixture_zip(&game_dir), &game_dir, &resolver, &NoProgress));
+        assert!(result.is_err());
+        assert!(!pack::instance_dir(&game_dir, "test-pack").exists());
+    }
+}
@@ -0,0 +1,313 @@
+// Modrinth's .mrpack : a zip with a modrinth.index.json listing the files to download,
+// and overrides/ copied over the instance. See https://docs.modrinth.com/docs/modpacks/format_definition/
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read, Seek, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+
+use crate::{
+    downloader::{Downloader, ProgressSink},
+    error::LauncherError,
+    installer::optional_mods::DISABLED_SUFFIX,
+    loader::ModLoader,
+    modrinth::ModrinthApi,
+    pack::{self, Pack, PackLoader, PackMod, Side},
+};
+
+use super::{add_overrides, extract_overrides, relative_path};
+
+const INDEX: &str = "modrinth.index.json";
+const OVERRIDES: [&str; 2] = ["overrides", "client-overrides"];
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModrinthIndex {
+    format_version: u32,
+    game: String,
+    version_id: String,
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    summary: Option<String>,
+    files: Vec<ModrinthFile>,
+    dependencies: HashMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModrinthFile {
+    path: String,
+    hashes: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    env: Option<ModrinthEnv>,
+    downloads: Vec<String>,
+    file_size: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ModrinthEnv {
+    client: EnvSupport,
+    server: EnvSupport,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum EnvSupport {
+    Required,
+    Optional,
+    Unsupported,
+}
+
+fn loader_id(loader: ModLoader) -> &'static str {
+    match loader {
+        ModLoader::Fabric => "fabric-loader",
+        ModLoader::Quilt => "quilt-loader",
+        ModLoader::Forge => "forge",
+        ModLoader::NeoForge => "neoforge",
+    }
+}
+
+// Only the first version of the format exists, and only for Minecraft
+fn read_index<R: Read + Seek>(archive: &mut zip::ZipArchive<R>) -> Result<ModrinthIndex, LauncherError> {
+    let mut entry = archive.by_name(INDEX)?;
+    let mut content = String::new();
+    entry.read_to_string(&mut content)?;
+    let index: ModrinthIndex = serde_json::from_str(&content)?;
+
+    if index.format_version != 1 {
+        return Err(LauncherError::ManifestParse(format!("Unsupported .mrpack format version {}", index.format_version)));
+    }
+    if index.game != "minecraft" {
+        return Err(LauncherError::ManifestParse(format!("{} is a pack for {}, not Minecraft", index.name, index.game)));
+    }
+    Ok(index)
+}
+
+// The env a file of this side gets, the way file_side reads it back
+fn file_env(side: Side, optional: bool) -> ModrinthEnv {
+    let support = if optional { EnvSupport::Optional } else { EnvSupport::Required };
+    match side {
+        Side::Client => ModrinthEnv { client: support, server: EnvSupport::Unsupported },
+        Side::Server => ModrinthEnv { client: EnvSupport::Unsupported, server: support },
+        Side::Both => ModrinthEnv { client: support, server: support },
+    }
+}
+
+// The side a file runs on and whether it's optional there, None when it runs nowhere
+fn file_side(env: Option<&ModrinthEnv>) -> Option<(Side, bool)> {
+    match env.map(|env| (env.client, env.server)) {
+        None => Some((Side::Both, false)),
+        Some((EnvSupport::Unsupported, EnvSupport::Unsupported)) => None,
+        Some((EnvSupport::Unsupported, server)) => Some((Side::Server, server == EnvSupport::Optional)),
+        Some((client, EnvSupport::Unsupported)) => Some((Side::Client, client == EnvSupport::Optional)),
+        Some((client, _)) => Some((Side::Both, client == EnvSupport::Optional)),
+    }
+}
+
+fn file_sha512(path: &Path) -> io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha512::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+// Creates instances/<name> from the pack, returns the instance name
+pub async fn import(mrpack: &Path, game_dir: &Path, sink: &dyn ProgressSink) -> Result<String, LauncherError> {
+    let mut archive = zip::ZipArchive::new(fs::File::open(mrpack)?)?;
+
+    let index = read_index(&mut archive)?;
+
+    let minecraft_version = index.dependencies
+        .get("minecraft")
+        .cloned()
+        .ok_or_else(|| LauncherError::ManifestParse(format!("{} doesn't depend on Minecraft", index.name)))?;
+
+    let loader = [ModLoader::Fabric, ModLoader::Quilt, ModLoader::Forge, ModLoader::NeoForge]
+        .into_iter()
+        .find_map(|kind| Some(PackLoader {
+            kind,
+            version: Some(index.dependencies.get(loader_id(kind))?.clone()),
+        }));
+
+    let instance = pack::instance_name(&index.name);
+    let instance_dir = pack::instance_dir(game_dir, &instance);
+    let mut downloader = Downloader::from_env();
+    let mut mods = Vec::new();
+
+    for file in index.files {
+        let Some((side, optional)) = file_side(file.env.as_ref()) else {
+            println!("Skipping {}, supported on no side", file.path);
+            continue
+        };
+        let url = file.downloads
+            .first()
+            .cloned()
+            .ok_or_else(|| LauncherError::ManifestParse(format!("{} has no download", file.path)))?;
+        let path = relative_path(&file.path)?;
+
+        // Only mods/ is part of the pack, the rest is a one time download like the overrides
+        match file.path.strip_prefix("mods/") {
+            Some(file_name) if !file_name.contains('/') => mods.push(PackMod {
+                id: None,
+                url,
+                file_name: Some(file_name.to_string()),
+                sha1: file.hashes
+                    .get("sha1")
+                    .cloned()
+                    .ok_or_else(|| LauncherError::ManifestParse(format!("{} has no sha1", file.path)))?,
+                size: file.file_size,
+                side,
+                optional,
+                default_enabled: true,
+            }),
+            // The instance is a client one
+            _ if side == Side::Server => {},
+            _ => downloader.push_to_queue(url,
+                                          instance_dir.join(path),
+                                          file.hashes.get("sha1").cloned(),
+                                          Some(file.file_size))?,
+        }
+    }
+
+    extract_overrides(&mut archive, &OVERRIDES, &instance_dir)?;
+
+    downloader.download_queue(sink).await?;
+
+    let pack = Pack {
+        name: index.name,
+        version: index.version_id,
+        minecraft_version,
+        loader,
+        mods,
+        modrinth: Vec::new(),
+        lock: None,
+        server_lock: None,
+    };
+    pack.save(&instance_dir)?;
+    println!("Imported {} as instance {}", pack.name, instance);
+
+    Ok(instance)
+}
+
+// The sha512 the format wants is taken from the installed mods, configs and the like go in overrides/
+pub async fn export(pack: &Pack, instance_dir: &Path, downloader: &Downloader, output: &Path) -> Result<(), LauncherError> {
+    let mut dependencies = HashMap::new();
+    dependencies.insert("minecraft".to_string(), pack.minecraft_version.clone());
+    if let Some(loader) = &pack.loader {
+        let version = loader.kind
+            .resolve_version(downloader, &pack.minecraft_version, loader.version.as_deref())
+            .await?;
+        dependencies.insert(loader_id(loader.kind).to_string(), version);
+    }
+
+    // The Modrinth entries are exported as the files they resolve to, like the installer sees them
+    let mut mods = pack.mods.clone();
+    if !pack.modrinth.is_empty() {
+        let resolved = ModrinthApi::new()?
+            .resolve(&pack.modrinth, &pack.minecraft_version, pack.loader.as_ref().map(|loader| loader.kind))
+            .await?;
+        mods.extend(resolved);
+    }
+
+    let mut files = Vec::new();
+    for modif in &mods {
+        let file_name = modif.local_name()?;
+        let mut installed = instance_dir.join("mods").join(&file_name);
+        if !installed.is_file() {
+            installed = instance_dir.join("mods").join(format!("{}{}", file_name, DISABLED_SUFFIX));
+        }
+
+        let sha512 = if installed.is_file() {
+            file_sha512(&installed)?
+        } else {
+            // Server mods aren't in a client instance, they're only downloaded to be hashed
+            let temp = std::env::temp_dir().join(format!("pblm-export-{}", file_name));
+            downloader.download_file(modif.url.clone(), &temp).await?;
+            let sha512 = file_sha512(&temp);
+            fs::remove_file(&temp)?;
+            sha512?
+        };
+
+        let env = file_env(modif.side, modif.optional);
+
+        files.push(ModrinthFile {
+            path: format!("mods/{}", file_name),
+            hashes: HashMap::from([("sha1".to_string(), modif.sha1.clone()), ("sha512".to_string(), sha512)]),
+            env: Some(env),
+            downloads: vec![modif.url.clone()],
+            file_size: modif.size,
+        });
+    }
+
+    let index = ModrinthIndex {
+        format_version: 1,
+        game: "minecraft".into(),
+        version_id: pack.version.clone(),
+        name: pack.name.clone(),
+        summary: None,
+        files,
+        dependencies,
+    };
+
+    let mut zip = zip::ZipWriter::new(fs::File::create(output)?);
+    zip.start_file(INDEX, zip::write::FileOptions::default())?;
+    zip.write_all(serde_json::to_string_pretty(&index)?.as_bytes())?;
+    add_overrides(&mut zip, instance_dir, OVERRIDES[0])?;
+    zip.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(client: EnvSupport, server: EnvSupport) -> ModrinthEnv {
+        ModrinthEnv { client, server }
+    }
+
+    #[test]
+    fn maps_env_to_sides() {
+        use EnvSupport::*;
+        assert_eq!(file_side(None), Some((Side::Both, false)));
+        assert_eq!(file_side(Some(&env(Required, Required))), Some((Side::Both, false)));
+        assert_eq!(file_side(Some(&env(Optional, Required))), Some((Side::Both, true)));
+        assert_eq!(file_side(Some(&env(Required, Unsupported))), Some((Side::Client, false)));
+        assert_eq!(file_side(Some(&env(Unsupported, Required))), Some((Side::Server, false)));
+        assert_eq!(file_side(Some(&env(Unsupported, Optional))), Some((Side::Server, true)));
+        assert_eq!(file_side(Some(&env(Unsupported, Unsupported))), None);
+    }
+
+    #[test]
+    fn exported_env_reads_back() {
+        for side in [Side::Client, Side::Server, Side::Both] {
+            for optional in [false, true] {
+                assert_eq!(file_side(Some(&file_env(side, optional))), Some((side, optional)));
+            }
+        }
+        let both = file_env(Side::Both, true);
+        assert_eq!((both.client, both.server), (EnvSupport::Optional, EnvSupport::Optional));
+    }
+
+    fn archive(index: &str) -> zip::ZipArchive<io::Cursor<Vec<u8>>> {
+        let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        zip.start_file(INDEX, zip::write::FileOptions::default()).unwrap();
+        zip.write_all(index.as_bytes()).unwrap();
+        zip::ZipArchive::new(zip.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn checks_format_and_game() {
+        let index = |format_version: u32, game: &str| format!(
+            r#"{{"formatVersion": {}, "game": "{}", "versionId": "1", "name": "Pack", "files": [], "dependencies": {{"minecraft": "1.20.1"}}}}"#,
+            format_version, game);
+
+        assert!(read_index(&mut archive(&index(1, "minecraft"))).is_ok());
+        assert!(matches!(read_index(&mut archive(&index(2, "minecraft"))), Err(LauncherError::ManifestParse(_))));
+        assert!(matches!(read_index(&mut archive(&index(1, "terraria"))), Err(LauncherError::ManifestParse(_))));
+    }
+}
@@ -0,0 +1,401 @@
+// Mods declared by Modrinth project rather than by URL, resolved with the v2 API into
+// the jars for the pack's game version and loader, required dependencies included.
+// See https://docs.modrinth.com/api/
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::LauncherError,
+    loader::ModLoader,
+    pack::{self, PackMod, Side},
+    version_range,
+};
+
+const MODRINTH_API: &str = "https://api.modrinth.com/v2";
+const USER_AGENT: &str = "Flagadar/PBLM-Launcher";
+
+// A pack entry : the project id or slug, and which of its versions to take
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModrinthMod {
+    pub project: String,
+    // None takes the latest release, see VersionConstraint for the rest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub side: Side,
+    #[serde(default)]
+    pub optional: bool,
+    // Whether an optional mod is on until the player says otherwise
+    #[serde(default = "pack::enabled_by_default")]
+    pub default_enabled: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Version {
+    id: String,
+    project_id: String,
+    version_number: String,
+    version_type: String,
+    #[serde(default)]
+    game_versions: Vec<String>,
+    #[serde(default)]
+    loaders: Vec<String>,
+    files: Vec<VersionFile>,
+    #[serde(default)]
+    dependencies: Vec<Dependency>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct VersionFile {
+    url: String,
+    filename: String,
+    #[serde(default)]
+    primary: bool,
+    size: u64,
+    hashes: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Dependency {
+    version_id: Option<String>,
+    project_id: Option<String>,
+    dependency_type: DependencyType,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum DependencyType {
+    Required,
+    Optional,
+    Incompatible,
+    Embedded,
+}
+
+// "1.2.3" or "=1.2.3" for that version only, "1.2.*" for any 1.2 one, ">=1.2" for 1.2 or newer
+#[derive(Clone)]
+enum VersionConstraint {
+    Latest,
+    Exact(String),
+    Prefix(String),
+    AtLeast(String),
+}
+
+impl VersionConstraint {
+    fn parse(constraint: Option<&str>) -> Self {
+        match constraint.map(str::trim) {
+            None | Some("") | Some("*") => VersionConstraint::Latest,
+            Some(constraint) => {
+                if let Some(version) = constraint.strip_prefix(">=") {
+                    VersionConstraint::AtLeast(version.trim().to_string())
+                } else if let Some(prefix) = constraint.strip_suffix('*') {
+                    VersionConstraint::Prefix(prefix.to_string())
+                } else {
+                    VersionConstraint::Exact(constraint.trim_start_matches('=').to_string())
+                }
+            },
+        }
+    }
+
+    fn matches(&self, version: &Version) -> bool {
+        match self {
+            VersionConstraint::Latest => version.version_type == "release",
+            VersionConstraint::Exact(wanted) => version.version_number == *wanted || version.id == *wanted,
+            VersionConstraint::Prefix(prefix) => version.version_number.starts_with(prefix.as_str()),
+            VersionConstraint::AtLeast(minimum) => version_range::compare(&version.version_number, minimum).is_ge(),
+        }
+    }
+}
+
+// Why a version is in the set, to tell whether another one of the same project can replace it
+enum Requirement {
+    // Declared by the pack, or a dependency on that exact version
+    Pinned,
+    Declared(VersionConstraint),
+    // A dependency on the project, any version does
+    Any,
+}
+
+impl Requirement {
+    fn accepts(&self, version: &Version) -> bool {
+        match self {
+            Requirement::Pinned => false,
+            Requirement::Declared(constraint) => constraint.matches(version),
+            Requirement::Any => true,
+        }
+    }
+}
+
+fn loader_names(loader: Option<ModLoader>) -> Vec<&'static str> {
+    match loader {
+        Some(ModLoader::Fabric) => vec!["fabric"],
+        // Quilt runs Fabric mods too
+        Some(ModLoader::Quilt) => vec!["quilt", "fabric"],
+        Some(ModLoader::Forge) => vec!["forge"],
+        Some(ModLoader::NeoForge) => vec!["neoforge"],
+        None => vec!["minecraft"],
+    }
+}
+
+// Versions taken by id don't go through the game version and loader filter of the API,
+// a Forge build could end up in a Fabric pack
+fn check_compatible(version: &Version, minecraft_version: &str, loaders: &[&str]) -> Result<(), LauncherError> {
+    let game_version = version.game_versions.iter().any(|game_version| game_version == minecraft_version);
+    let loader = version.loaders.iter().any(|loader| loaders.contains(&loader.as_str()));
+    if game_version && loader {
+        return Ok(())
+    }
+    Err(LauncherError::ManifestParse(format!(
+                "{} {} is for {} on {}, not {} on {}",
+                version.project_id,
+                version.version_number,
+                version.loaders.join("/"),
+                version.game_versions.join(", "),
+                loaders.join("/"),
+                minecraft_version)))
+}
+
+pub struct ModrinthApi {
+    client: reqwest::Client,
+}
+
+impl ModrinthApi {
+    pub fn new() -> Result<Self, LauncherError> {
+        let client = reqwest::Client::builder()
+            .user_agent(format!("{}/{}", USER_AGENT, env!("CARGO_PKG_VERSION")))
+            .build()?;
+        Ok(Self { client })
+    }
+
+    async fn get<T: serde::de::DeserializeOwned>(&self, url: String) -> Result<T, LauncherError> {
+        Ok(self.client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    // Newest first, only the ones for this game version and loader
+    async fn project_versions(&self, project: &str, minecraft_version: &str, loaders: &[&str]) -> Result<Vec<Version>, LauncherError> {
+        self.get(format!("{}/project/{}/version?game_versions={}&loaders={}",
+                         MODRINTH_API,
+                         project,
+                         serde_json::to_string(&[minecraft_version])?,
+                         serde_json::to_string(loaders)?))
+            .await
+    }
+
+    async fn version(&self, id: &str) -> Result<Version, LauncherError> {
+        self.get(format!("{}/version/{}", MODRINTH_API, id)).await
+    }
+
+    // By id or version number, whatever game versions and loaders it lists
+    async fn project_version(&self, project: &str, version: &str) -> Result<Version, LauncherError> {
+        self.get(format!("{}/project/{}/version/{}", MODRINTH_API, project, version)).await
+    }
+
+    // Every declared mod and its required dependencies, one version per project.
+    // Fails when a mod is declared incompatible with another one of the set
+    pub async fn resolve(&self, mods: &[ModrinthMod], minecraft_version: &str, loader: Option<ModLoader>) -> Result<Vec<PackMod>, LauncherError> {
+        let loaders = loader_names(loader);
+        let mut selected: HashMap<String, (Version, Requirement)> = HashMap::new();
+        let mut order: Vec<String> = Vec::new();
+        let mut roots: Vec<(String, &ModrinthMod)> = Vec::new();
+        let mut requires: HashMap<String, Vec<String>> = HashMap::new();
+        let mut incompatible: Vec<(String, Dependency)> = Vec::new();
+
+        // The versions left to go through, with the project that wanted them. Declared
+        // mods come first so that their constraint wins over what dependencies ask for
+        let mut pending: VecDeque<(Option<String>, Version, Requirement)> = VecDeque::new();
+
+        for modif in mods {
+            let constraint = VersionConstraint::parse(modif.version.as_deref());
+            // A pinned version is taken as is, the pack author picked it
+            if let VersionConstraint::Exact(wanted) = &constraint {
+                let version = self.project_version(&modif.project, wanted).await?;
+                check_compatible(&version, minecraft_version, &loaders)?;
+                roots.push((version.project_id.clone(), modif));
+                pending.push_back((None, version, Requirement::Pinned));
+                continue
+            }
+
+            let versions = self.project_versions(&modif.project, minecraft_version, &loaders).await?;
+            let version = versions
+                .iter()
+                .find(|version| constraint.matches(version))
+                .or_else(|| match constraint {
+                    // No release at all, the latest beta will do
+                    VersionConstraint::Latest => versions.first(),
+                    _ => None,
+                })
+                .cloned()
+                .ok_or_else(|| LauncherError::ManifestParse(format!(
+                            "No version of {} matches {} for Minecraft {}",
+                            modif.project,
+                            modif.version.as_deref().unwrap_or("*"),
+                            minecraft_version)))?;
+            roots.push((version.project_id.clone(), modif));
+            pending.push_back((None, version, Requirement::Declared(constraint)));
+        }
+
+        while let Some((wanted_by, version, requirement)) = pending.pop_front() {
+            if let Some(parent) = &wanted_by {
+                requires.entry(parent.clone()).or_default().push(version.project_id.clone());
+            }
+
+            // A dependency on the project is satisfied by whatever version is there. One on
+            // another version replaces it when what put it there allows, and conflicts otherwise
+            if let Some((existing, existing_requirement)) = selected.get(&version.project_id) {
+                if existing.id == version.id || matches!(requirement, Requirement::Any) {
+                    continue
+                }
+                if !existing_requirement.accepts(&version) {
+                    return Err(LauncherError::ModConflict(format!(
+                                "{} needs {} {} but {} is required",
+                                wanted_by.as_deref().unwrap_or("The pack"),
+                                version.project_id,
+                                version.version_number,
+                                existing.version_number)));
+                }
+                println!("Replacing {} {} with {}", version.project_id, existing.version_number, version.version_number);
+            } else {
+                order.push(version.project_id.clone());
+            }
+
+            for dependency in &version.dependencies {
+                match dependency.dependency_type {
+                    DependencyType::Required => {
+                        let (dependency_version, dependency_requirement) = match (&dependency.version_id, &dependency.project_id) {
+                            (Some(version_id), _) => {
+                                let pinned = self.version(version_id).await?;
+                                check_compatible(&pinned, minecraft_version, &loaders)?;
+                                (pinned, Requirement::Pinned)
+                            },
+                            (None, Some(project_id)) => {
+                                if selected.contains_key(project_id) {
+                                    requires.entry(version.project_id.clone()).or_default().push(project_id.clone());
+                                    continue
+                                }
+                                let latest = self
+                                    .project_versions(project_id, minecraft_version, &loaders)
+                                    .await?
+                                    .into_iter()
+                                    .next()
+                                    .ok_or_else(|| LauncherError::ManifestParse(format!(
+                                                "{} needs {} which has no version for Minecraft {}",
+                                                version.project_id, project_id, minecraft_version)))?;
+                                (latest, Requirement::Any)
+                            },
+                            (None, None) => continue,
+                        };
+                        pending.push_back((Some(version.project_id.clone()), dependency_version, dependency_requirement));
+                    },
+                    DependencyType::Incompatible => incompatible.push((version.project_id.clone(), dependency.clone())),
+                    DependencyType::Optional | DependencyType::Embedded => {},
+                }
+            }
+
+            selected.insert(version.project_id.clone(), (version, requirement));
+        }
+
+        // Dependencies take the side of the mods needing them, stay optional only as long
+        // as every mod needing them is, and are on by default if any of them is
+        let mut flags: HashMap<String, (Side, bool, bool)> = HashMap::new();
+        let mut ids: HashMap<String, String> = HashMap::new();
+        for (root, modif) in &roots {
+            ids.entry(root.clone()).or_insert_with(|| modif.project.clone());
+            let mut stack = vec![root.clone()];
+            let mut seen = HashSet::new();
+            while let Some(project) = stack.pop() {
+                if !seen.insert(project.clone()) {
+                    continue
+                }
+                flags.entry(project.clone())
+                    .and_modify(|(project_side, project_optional, project_enabled)| {
+                        if *project_side != modif.side {
+                            *project_side = Side::Both;
+                        }
+                        *project_optional &= modif.optional;
+                        *project_enabled |= modif.default_enabled;
+                    })
+                    .or_insert((modif.side, modif.optional, modif.default_enabled));
+                stack.extend(requires.get(&project).into_iter().flatten().cloned());
+            }
+        }
+
+        let selected_versions: HashSet<&str> = selected.values().map(|(version, _)| version.id.as_str()).collect();
+        for (project, dependency) in &incompatible {
+            let conflicts = dependency.project_id.as_ref().is_some_and(|id| selected.contains_key(id))
+                || dependency.version_id.as_ref().is_some_and(|id| selected_versions.contains(id.as_str()));
+            if conflicts {
+                return Err(LauncherError::ModConflict(format!(
+                            "{} is incompatible with {}",
+                            project,
+                            dependency.project_id.as_ref().or(dependency.version_id.as_ref()).map_or("", |id| id.as_str()))));
+            }
+        }
+
+        order
+            .iter()
+            .filter_map(|project| selected.get(project))
+            .map(|(version, _)| {
+                let file = version.files
+                    .iter()
+                    .find(|file| file.primary)
+                    .or(version.files.first())
+                    .ok_or_else(|| LauncherError::ManifestParse(format!(
+                                "{} {} has no file", version.project_id, version.version_number)))?;
+                let (side, optional, default_enabled) = flags
+                    .get(&version.project_id)
+                    .copied()
+                    .unwrap_or((Side::Both, false, true));
+                Ok(PackMod {
+                    // Declared mods keep the name the pack uses for them
+                    id: Some(ids.get(&version.project_id).unwrap_or(&version.project_id).clone()),
+                    url: file.url.clone(),
+                    file_name: Some(file.filename.clone()),
+                    sha1: file.hashes
+                        .get("sha1")
+                        .cloned()
+                        .ok_or_else(|| LauncherError::ManifestParse(format!("{} has no sha1", file.filename)))?,
+                    size: file.size,
+                    side,
+                    optional,
+                    default_enabled,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(loaders: &[&str], game_versions: &[&str]) -> Version {
+        serde_json::from_value(serde_json::json!({
+            "id": "3GURrv52",
+            "project_id": "Wnxd13zP",
+            "version_number": "9.0.0+14",
+            "version_type": "release",
+            "game_versions": game_versions,
+            "loaders": loaders,
+            "files": [],
+        })).unwrap()
+    }
+
+    #[test]
+    fn pinned_versions_must_fit_the_pack() {
+        let fabric = loader_names(Some(ModLoader::Fabric));
+        assert!(check_compatible(&version(&["fabric"], &["1.19.2"]), "1.19.2", &fabric).is_ok());
+        assert!(check_compatible(&version(&["forge"], &["1.19.2"]), "1.19.2", &fabric).is_err());
+        assert!(check_compatible(&version(&["fabric"], &["1.19.3"]), "1.19.2", &fabric).is_err());
+        // Quilt takes Fabric builds
+        let quilt = loader_names(Some(ModLoader::Quilt));
+        assert!(check_compatible(&version(&["fabric"], &["1.19.2"]), "1.19.2", &quilt).is_ok());
+    }
+}
@@ -0,0 +1,267 @@
+// What gets installed : the Minecraft version, the mod loader and the mods,
+// everything else follows from the version file of minecraft_version.
+// The manifest is fetched at each launch so the pack changes without a launcher release,
+// and has to be signed with minisign since it decides which jars our players run
+
+use std::{
+    env,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use minisign_verify::{PublicKey, Signature};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    downloader::{self, Downloader},
+    error::LauncherError,
+    loader::ModLoader,
+    modrinth::ModrinthMod,
+    version_range,
+};
+
+const PACK_MANIFEST: &str = "https://raw.githubusercontent.com/Flagadar/PBLM-Launcher/master/pack.json";
+const PACK_MANIFEST_ENV: &str = "PBLM_PACK_URL";
+const PACK_CACHE: &str = "pack.json";
+const PACK_SIGNATURE_CACHE: &str = "pack.json.minisig";
+// Local packs, imported rather than fetched, each in its own instances/<name>
+const INSTANCES_DIR: &str = "instances";
+const INSTANCE_PACK: &str = "pack.json";
+// Same key as the updater one in tauri.conf.json
+const PACK_PUBLIC_KEY: &str = "RWT+gUmcoobwwCNryObxIFuSlcOnXaiS34qLWCJIQ02l5WLdvc0q/yPR";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Pack {
+    pub name: String,
+    // Bumped with every change to the pack
+    pub version: String,
+    pub minecraft_version: String,
+    // None installs vanilla
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loader: Option<PackLoader>,
+    #[serde(default)]
+    pub mods: Vec<PackMod>,
+    // Resolved with their dependencies at install, next to the mods above
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modrinth: Vec<ModrinthMod>,
+    // When set, the install is strictly the one described by this lockfile
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lock: Option<PackLock>,
+    // The same for dedicated servers, what install_server writes in the server directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_lock: Option<PackLock>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackLock {
+    pub url: String,
+    pub sha1: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackLoader {
+    pub kind: ModLoader,
+    // None picks the latest stable loader
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackMod {
+    // What optional mods are toggled by, defaults to the file name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub url: String,
+    // Defaults to the last segment of the URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_name: Option<String>,
+    // Required, a file already in mods/ is only kept when it matches them
+    pub sha1: String,
+    pub size: u64,
+    #[serde(default)]
+    pub side: Side,
+    #[serde(default)]
+    pub optional: bool,
+    // Whether an optional mod is on until the player says otherwise
+    #[serde(default = "enabled_by_default")]
+    pub default_enabled: bool,
+}
+
+pub fn enabled_by_default() -> bool {
+    true
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Client,
+    Server,
+    #[default]
+    Both,
+}
+
+// What an install is for. Client mods stay off servers and server mods off players' games
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InstallSide {
+    #[default]
+    Client,
+    Server,
+}
+
+impl Side {
+    pub fn runs_on(self, install: InstallSide) -> bool {
+        match self {
+            Side::Client => install == InstallSide::Client,
+            Side::Server => install == InstallSide::Server,
+            Side::Both => true,
+        }
+    }
+}
+
+impl PackMod {
+    pub fn local_name(&self) -> Result<String, LauncherError> {
+        self.file_name
+            .clone()
+            .or_else(|| downloader::url_file_name(&self.url))
+            .ok_or_else(|| LauncherError::ManifestParse(format!("Unable to name mod {}", self.url)))
+    }
+
+    pub fn key(&self) -> Result<String, LauncherError> {
+        match &self.id {
+            Some(id) => Ok(id.clone()),
+            None => self.local_name(),
+        }
+    }
+}
+
+// PBLM_PACK_URL points the launcher at another pack, for testing one before publishing it
+pub fn manifest_url() -> String {
+    env::var(PACK_MANIFEST_ENV).unwrap_or_else(|_| PACK_MANIFEST.to_string())
+}
+
+pub fn instance_dir(game_dir: &Path, instance: &str) -> PathBuf {
+    game_dir.join(INSTANCES_DIR).join(instance)
+}
+
+// Instance directory name for a pack name
+pub fn instance_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect::<String>()
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+// The signature is the minisign .minisig file published next to the manifest
+fn verify(manifest: &str, signature: &str) -> Result<(), LauncherError> {
+    let public_key = PublicKey::from_base64(PACK_PUBLIC_KEY)
+        .map_err(|err| LauncherError::Signature(err.to_string()))?;
+    let signature = Signature::decode(signature)
+        .map_err(|err| LauncherError::Signature(err.to_string()))?;
+
+    public_key
+        // tauri signer doesn't always prehash, both forms are safe
+        .verify(manifest.as_bytes(), &signature, true)
+        .map_err(|err| LauncherError::Signature(err.to_string()))
+}
+
+impl Pack {
+    // The last manifest fetched is kept in the game directory for offline launches,
+    // with its signature so that it is checked again each time. A signed manifest older
+    // than the cached one is refused, otherwise an old one could be served to roll players back
+    pub async fn fetch(downloader: &Downloader, game_dir: &Path, url: &str) -> Result<Self, LauncherError> {
+        let cache_path = game_dir.join(PACK_CACHE);
+        let signature_cache_path = game_dir.join(PACK_SIGNATURE_CACHE);
+
+        let fetched = match downloader.to_text(url.to_string()).await {
+            Ok(manifest) => downloader
+                .to_text(format!("{}.minisig", url))
+                .await
+                .map(|signature| (manifest, signature)),
+            Err(err) => Err(err),
+        };
+
+        // The signature is pushed after the manifest and both are cached apart, a manifest
+        // failing its check is most likely a new one whose signature isn't there yet
+        let checked = fetched.and_then(|(manifest, signature)| {
+            let pack = Self::check(&manifest, &signature, &cache_path, &signature_cache_path)?;
+            Ok((manifest, signature, pack))
+        });
+
+        let pack = match checked {
+            Ok((manifest, signature, pack)) => {
+                fs::write(&cache_path, &manifest)?;
+                fs::write(&signature_cache_path, &signature)?;
+                pack
+            },
+            Err(err) if cache_path.is_file() && signature_cache_path.is_file() => {
+                println!("Unable to use the fetched pack manifest, using the cached one : {}", err);
+                Self::load_cache(&cache_path, &signature_cache_path)?
+            },
+            Err(err) => return Err(err),
+        };
+
+        println!("Using {} version {}", pack.name, pack.version);
+        Ok(pack)
+    }
+
+    fn check(manifest: &str, signature: &str, cache_path: &Path, signature_cache_path: &Path) -> Result<Self, LauncherError> {
+        verify(manifest, signature)?;
+        let pack: Pack = serde_json::from_str(manifest)?;
+
+        match Self::load_cache(cache_path, signature_cache_path) {
+            Ok(cached) if cached.name == pack.name
+                && version_range::compare(&pack.version, &cached.version).is_lt() => {
+                Err(LauncherError::Signature(format!(
+                            "The pack manifest went back from version {} to {}",
+                            cached.version, pack.version)))
+            },
+            _ => Ok(pack),
+        }
+    }
+
+    fn load_cache(cache_path: &Path, signature_cache_path: &Path) -> Result<Self, LauncherError> {
+        let manifest = fs::read_to_string(cache_path)?;
+        verify(&manifest, &fs::read_to_string(signature_cache_path)?)?;
+        Ok(serde_json::from_str(&manifest)?)
+    }
+
+    // Instances are trusted as they are, players imported them themselves
+    pub fn load(instance_dir: &Path) -> Result<Self, LauncherError> {
+        Ok(serde_json::from_str(&fs::read_to_string(instance_dir.join(INSTANCE_PACK))?)?)
+    }
+
+    pub fn save(&self, instance_dir: &Path) -> Result<(), LauncherError> {
+        fs::create_dir_all(instance_dir)?;
+        fs::write(instance_dir.join(INSTANCE_PACK), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn published_pack_parses() {
+        let pack: Pack = serde_json::from_str(include_str!("../../pack.json")).unwrap();
+        assert!(pack.mods.is_empty());
+        assert!(pack.modrinth.iter().all(|modif| modif.version.is_some()));
+    }
+
+    #[test]
+    fn mods_need_hashes() {
+        let missing = r#"{ "url": "https://cdn.modrinth.com/data/a/versions/b/mod.jar" }"#;
+        assert!(serde_json::from_str::<PackMod>(missing).is_err());
+
+        let pinned = r#"{ "url": "https://cdn.modrinth.com/data/a/versions/b/mod.jar", "sha1": "da39a3ee5e6b4b0d3255bfef95601890afd80709", "size": 0 }"#;
+        let modif: PackMod = serde_json::from_str(pinned).unwrap();
+        assert_eq!(modif.local_name().unwrap(), "mod.jar");
+        assert_eq!(modif.side, Side::Both);
+    }
+}
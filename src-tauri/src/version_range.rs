@@ -0,0 +1,132 @@
+// Loose version comparison, mods don't all follow semver
+
+use std::cmp::Ordering;
+
+// Dot separated parts compared as numbers when they are, as text otherwise. Like semver,
+// what follows the first "-" is a pre-release, older than the release itself
+pub fn compare(a: &str, b: &str) -> Ordering {
+    let (a_release, a_pre) = split_pre_release(strip_build(a));
+    let (b_release, b_pre) = split_pre_release(strip_build(b));
+
+    compare_parts(a_release, b_release).then_with(|| match (a_pre, b_pre) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a), Some(b)) => compare_parts(a, b),
+    })
+}
+
+fn split_pre_release(version: &str) -> (&str, Option<&str>) {
+    match version.split_once('-') {
+        Some((release, pre_release)) => (release, Some(pre_release)),
+        None => (version, None),
+    }
+}
+
+fn compare_parts(a: &str, b: &str) -> Ordering {
+    let split = |version: &str| -> Vec<String> {
+        version
+            .split(['.', '-'])
+            .map(String::from)
+            .collect()
+    };
+
+    for (a, b) in split(a).iter().zip(split(b).iter()) {
+        let ordering = match (a.parse::<u64>(), b.parse::<u64>()) {
+            (Ok(a), Ok(b)) => a.cmp(&b),
+            _ => a.cmp(b),
+        };
+        if ordering.is_ne() {
+            return ordering
+        }
+    }
+    split(a).len().cmp(&split(b).len())
+}
+
+// Build metadata doesn't take part in comparisons
+fn strip_build(version: &str) -> &str {
+    version.split('+').next().unwrap_or(version)
+}
+
+fn parts(version: &str) -> Vec<&str> {
+    strip_build(version).split('.').collect()
+}
+
+// Fabric's version predicates : "*", "1.19.x", "=1.2", ">=1.2 <2", "~1.2.3" (same minor), "^1.2" (same major).
+// Space separated predicates all have to match
+pub fn matches(range: &str, version: &str) -> bool {
+    let version = strip_build(version);
+    range.split_whitespace().all(|predicate| matches_predicate(predicate, version))
+}
+
+fn matches_predicate(predicate: &str, version: &str) -> bool {
+    if predicate == "*" {
+        return true
+    }
+
+    let (operator, wanted) = match predicate.find(|c: char| c.is_ascii_alphanumeric()) {
+        Some(start) => predicate.split_at(start),
+        None => return false,
+    };
+    let wanted = strip_build(wanted);
+    let ordering = compare(version, wanted);
+
+    match operator {
+        ">=" => ordering.is_ge(),
+        "<=" => ordering.is_le(),
+        ">" => ordering.is_gt(),
+        "<" => ordering.is_lt(),
+        "~" => ordering.is_ge() && parts(version).iter().take(2).eq(parts(wanted).iter().take(2)),
+        "^" => ordering.is_ge() && parts(version).first() == parts(wanted).first(),
+        "" | "=" => {
+            let wanted = parts(wanted);
+            let version = parts(version);
+            match wanted.iter().position(|part| matches!(*part, "x" | "X" | "*")) {
+                Some(wildcard) => version.len() >= wildcard && wanted[..wildcard] == version[..wildcard],
+                None => compare(&version.join("."), &wanted.join(".")).is_eq(),
+            }
+        },
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_numerically() {
+        assert_eq!(compare("1.10", "1.9"), Ordering::Greater);
+        assert_eq!(compare("0.14.21", "0.14.21"), Ordering::Equal);
+        assert_eq!(compare("1.19", "1.19.2"), Ordering::Less);
+    }
+
+    #[test]
+    fn pre_releases_come_before_their_release() {
+        assert_eq!(compare("0.5.1-b", "0.5.1"), Ordering::Less);
+        assert_eq!(compare("0.5.1", "0.5.1-b"), Ordering::Greater);
+        assert_eq!(compare("0.5.1-b", "0.5.0"), Ordering::Greater);
+        assert_eq!(compare("1.0.0-beta.2", "1.0.0-beta.10"), Ordering::Less);
+        assert_eq!(compare("1.0.0-alpha", "1.0.0-beta"), Ordering::Less);
+    }
+
+    #[test]
+    fn ignores_build_metadata() {
+        assert_eq!(compare("0.76.0+1.19.2", "0.76.0+1.19.3"), Ordering::Equal);
+        assert_eq!(compare("0.5.1-b.1089+mc1.19.2", "0.5.1"), Ordering::Less);
+    }
+
+    #[test]
+    fn matches_fabric_predicates() {
+        assert!(matches("*", "1.0.0"));
+        assert!(matches("1.19.x", "1.19.2"));
+        assert!(!matches("1.19.x", "1.20"));
+        assert!(matches(">=0.14.0 <0.15", "0.14.21"));
+        assert!(!matches(">=0.5.1", "0.5.1-b"));
+        assert!(matches("~1.2.3", "1.2.9"));
+        assert!(!matches("~1.2.3", "1.3.0"));
+        assert!(matches("^1.2", "1.9"));
+        assert!(!matches("^1.2", "2.0"));
+        assert!(matches("=0.76.0", "0.76.0+1.19.2"));
+    }
+}
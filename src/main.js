@@ -2,6 +2,9 @@ const { invoke } = window.__TAURI__.tauri;
 const { listen } = window.__TAURI__.event;
 
 let logEl;
+let progressEl;
+let progressBarEl;
+let progressStatusEl;
 
 listen('tauri://update-status', function (res) {
     console.log(res);
@@ -12,7 +15,54 @@ async function launch() {
     await listen("launcher-log", (e) => {
         logEl.textContent = e.payload;
     });
-    await invoke("launch");
+    progressEl = document.querySelector("#download-progress");
+    progressBarEl = document.querySelector("#download-progress-bar");
+    progressStatusEl = document.querySelector("#download-status");
+    await listen("download-progress", (e) => {
+        showProgress(e.payload);
+    });
+    try {
+        await invoke("launch");
+    } catch (err) {
+        // LauncherError payload : { kind, message }
+        console.error(err);
+        progressEl.hidden = true;
+        progressStatusEl.hidden = true;
+        logEl.textContent = "Erreur : " + err.message;
+    }
+}
+
+function formatBytes(bytes) {
+    if (bytes >= 1024 * 1024) {
+        return (bytes / (1024 * 1024)).toFixed(1) + " Mo";
+    }
+    return Math.round(bytes / 1024) + " Ko";
+}
+
+function showProgress(progress) {
+    progressEl.hidden = false;
+    progressStatusEl.hidden = false;
+
+    let ratio = 0;
+    if (progress.totalBytes > 0) {
+        ratio = progress.receivedBytes / progress.totalBytes;
+    } else if (progress.totalFiles > 0) {
+        ratio = progress.completedFiles / progress.totalFiles;
+    }
+    progressBarEl.style.width = Math.min(100, ratio * 100) + "%";
+
+    let status = progress.completedFiles + "/" + progress.totalFiles + " fichiers - "
+        + formatBytes(progress.bytesPerSecond) + "/s";
+    if (progress.etaSeconds !== null) {
+        status += " - " + progress.etaSeconds + " s restantes";
+    }
+    progressStatusEl.textContent = status;
+    progressStatusEl.title = progress.currentFile ?? "";
+
+    if (progress.totalFiles > 0 && progress.completedFiles === progress.totalFiles) {
+        progressEl.hidden = true;
+        progressStatusEl.hidden = true;
+    }
 }
 
 window.addEventListener("DOMContentLoaded", () => {